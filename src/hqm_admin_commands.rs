@@ -2,20 +2,23 @@ extern crate crypto;
 
 use crate::hqm_admin_commands::crypto::digest::Digest;
 use crate::hqm_game::{
-    HQMGameObject, HQMGameState, HQMGameWorld, HQMMessage, HQMRink, HQMTeam, RHQMGamePlayer,
-    RHQMPlayer,
+    HQMBreakReason, HQMGameObject, HQMGameState, HQMGameWorld, HQMMessage, HQMRink, HQMTeam,
+    RHQMGamePlayer, RHQMPlayer,
 };
 use crate::hqm_server::{
-    HQMIcingConfiguration, HQMMuteStatus, HQMOffsideConfiguration, HQMServer, HQMServerMode,
-    HQMSpawnPoint,
+    chat_lines, load_bans, split_chat_message, HQMIcingConfiguration, HQMMuteStatus,
+    HQMOffsideConfiguration, HQMServer, HQMServerMode, HQMServerReceivedData, HQMSpawnPoint,
+    DEFAULT_PLAYER_NAME,
 };
 use crypto::md5::Md5;
 use nalgebra::{Matrix3, Point3};
 use postgres::{Connection, SslMode};
 use rand::seq::SliceRandom;
 use rand::Rng;
+use std::convert::TryFrom;
 use std::net::SocketAddr;
-use tracing::info;
+use std::sync::atomic::Ordering;
+use tracing::{info, warn};
 
 impl HQMServer {
     fn admin_deny_message(&mut self, player_index: usize) {
@@ -26,6 +29,7 @@ impl HQMServer {
     pub(crate) fn set_allow_join(&mut self, player_index: usize, allowed: bool) {
         if let Some(player) = &self.players[player_index] {
             if player.is_admin {
+                self.audit_admin_command(player_index, None);
                 self.allow_join = allowed;
 
                 if allowed {
@@ -46,6 +50,7 @@ impl HQMServer {
     pub(crate) fn mute_player(&mut self, admin_player_index: usize, mute_player_index: usize) {
         if let Some(admin_player) = &self.players[admin_player_index] {
             if admin_player.is_admin {
+                self.audit_admin_command(admin_player_index, Some(mute_player_index));
                 let admin_player_name = admin_player.player_name.clone();
 
                 if mute_player_index < self.players.len() {
@@ -72,6 +77,7 @@ impl HQMServer {
     pub(crate) fn unmute_player(&mut self, admin_player_index: usize, mute_player_index: usize) {
         if let Some(admin_player) = &self.players[admin_player_index] {
             if admin_player.is_admin {
+                self.audit_admin_command(admin_player_index, Some(mute_player_index));
                 let admin_player_name = admin_player.player_name.clone();
 
                 if mute_player_index < self.players.len() {
@@ -110,6 +116,7 @@ impl HQMServer {
     ) {
         if let Some(admin_player) = &self.players[admin_player_index] {
             if admin_player.is_admin {
+                self.audit_admin_command(admin_player_index, Some(mute_player_index));
                 let admin_player_name = admin_player.player_name.clone();
 
                 if mute_player_index < self.players.len() {
@@ -147,6 +154,7 @@ impl HQMServer {
     pub(crate) fn mute_chat(&mut self, player_index: usize) {
         if let Some(player) = &self.players[player_index] {
             if player.is_admin {
+                self.audit_admin_command(player_index, None);
                 self.is_muted = true;
 
                 let msg = format!("Chat muted by {}", player.player_name);
@@ -161,6 +169,7 @@ impl HQMServer {
     pub(crate) fn unmute_chat(&mut self, player_index: usize) {
         if let Some(player) = &self.players[player_index] {
             if player.is_admin {
+                self.audit_admin_command(player_index, None);
                 self.is_muted = false;
 
                 let msg = format!("Chat unmuted by {}", player.player_name);
@@ -257,11 +266,30 @@ impl HQMServer {
 
     pub(crate) fn admin_login(&mut self, player_index: usize, password: &str) {
         if let Some(player) = &mut self.players[player_index] {
-            if self.config.password == password {
+            let is_head_admin = self.config.head_password.as_deref() == Some(password);
+            if self.config.password == password || is_head_admin {
                 player.is_admin = true;
-                info!("{} ({}) is now admin", player.player_name, player_index);
-                let msg = format!("{} admin", player.player_name);
+                player.is_head_admin = is_head_admin;
+                let msg = if is_head_admin {
+                    info!(
+                        "{} ({}) is now head admin",
+                        player.player_name, player_index
+                    );
+                    format!("{} head admin", player.player_name)
+                } else {
+                    info!("{} ({}) is now admin", player.player_name, player_index);
+                    format!("{} admin", player.player_name)
+                };
                 self.add_server_chat_message(msg);
+
+                if self.admin_paused {
+                    self.admin_paused = false;
+                    if !self.game.paused_by_admin {
+                        self.game.paused = false;
+                        info!("Game resumed, admin present");
+                        self.add_server_chat_message(String::from("Game resumed, admin present"));
+                    }
+                }
             } else {
                 info!(
                     "{} ({}) tried to become admin, entered wrong password",
@@ -281,6 +309,7 @@ impl HQMServer {
     ) {
         if let Some(player) = &self.players[admin_player_index] {
             if player.is_admin {
+                self.audit_admin_command(admin_player_index, None);
                 let admin_player_name = player.player_name.clone();
 
                 // 0 full string | 1 begins with | 2 ends with | 3 contains
@@ -449,6 +478,7 @@ impl HQMServer {
     ) {
         if let Some(player) = &self.players[admin_player_index] {
             if player.is_admin {
+                self.audit_admin_command(admin_player_index, Some(kick_player_index));
                 let admin_player_name = player.player_name.clone();
 
                 if kick_player_index != admin_player_index {
@@ -501,9 +531,165 @@ impl HQMServer {
         }
     }
 
+    pub(crate) fn kick_noname(&mut self, admin_player_index: usize) {
+        if let Some(player) = &self.players[admin_player_index] {
+            if player.is_admin {
+                self.audit_admin_command(admin_player_index, None);
+                let admin_player_name = player.player_name.clone();
+
+                let mut kick_player_list = vec![];
+                for (player_index, p) in self.players.iter().enumerate() {
+                    if let Some(player) = p {
+                        if player.player_name == DEFAULT_PLAYER_NAME
+                            && player_index != admin_player_index
+                        {
+                            kick_player_list.push(player_index);
+                        }
+                    }
+                }
+
+                for player_index in kick_player_list.iter() {
+                    self.remove_player(*player_index);
+                }
+
+                info!(
+                    "{} ({}) kicked {} unnamed players",
+                    admin_player_name,
+                    admin_player_index,
+                    kick_player_list.len()
+                );
+                let msg = format!(
+                    "{} unnamed players kicked by {}",
+                    kick_player_list.len(),
+                    admin_player_name
+                );
+                self.add_server_chat_message(msg);
+            } else {
+                self.admin_deny_message(admin_player_index);
+            }
+        }
+    }
+
+    pub(crate) fn set_auto_view(
+        &mut self,
+        admin_player_index: usize,
+        auto_view_player_index: usize,
+    ) {
+        if let Some(player) = &self.players[admin_player_index] {
+            if player.is_admin {
+                self.audit_admin_command(admin_player_index, Some(auto_view_player_index));
+                let admin_player_name = player.player_name.clone();
+                if let Some(auto_view_player) = &mut self.players[auto_view_player_index] {
+                    auto_view_player.auto_view = !auto_view_player.auto_view;
+                    let enabled = auto_view_player.auto_view;
+                    if !enabled {
+                        auto_view_player.view_player_index = auto_view_player_index;
+                    }
+                    let auto_view_player_name = auto_view_player.player_name.clone();
+
+                    info!(
+                        "{} ({}) set auto view for {} ({}) to {}",
+                        admin_player_name,
+                        admin_player_index,
+                        auto_view_player_name,
+                        auto_view_player_index,
+                        enabled
+                    );
+                    let msg = if enabled {
+                        format!("Auto view enabled for {}", auto_view_player_name)
+                    } else {
+                        format!("Auto view disabled for {}", auto_view_player_name)
+                    };
+                    self.add_directed_server_chat_message(msg, admin_player_index);
+                }
+            } else {
+                self.admin_deny_message(admin_player_index);
+            }
+        }
+    }
+
+    pub(crate) fn activity(&mut self, admin_player_index: usize, activity_player_index: usize) {
+        if let Some(player) = &self.players[admin_player_index] {
+            if player.is_admin {
+                self.audit_admin_command(admin_player_index, Some(activity_player_index));
+                let msg = match &self.players[activity_player_index] {
+                    Some(activity_player) => format!(
+                        "{}: {} ticks since last packet",
+                        activity_player.player_name, activity_player.inactivity
+                    ),
+                    None => "No player with this ID exists".to_string(),
+                };
+                self.add_directed_server_chat_message(msg, admin_player_index);
+            } else {
+                self.admin_deny_message(admin_player_index);
+            }
+        }
+    }
+
+    pub(crate) fn wake(&mut self, admin_player_index: usize, wake_player_index: usize) {
+        if let Some(player) = &self.players[admin_player_index] {
+            if player.is_admin {
+                self.audit_admin_command(admin_player_index, Some(wake_player_index));
+                let admin_player_name = player.player_name.clone();
+                if let Some(wake_player) = &mut self.players[wake_player_index] {
+                    wake_player.inactivity = 0;
+                    let wake_player_name = wake_player.player_name.clone();
+
+                    info!(
+                        "{} ({}) reset inactivity of {} ({})",
+                        admin_player_name, admin_player_index, wake_player_name, wake_player_index
+                    );
+                    let msg = format!("Inactivity of {} reset", wake_player_name);
+                    self.add_directed_server_chat_message(msg, admin_player_index);
+                } else {
+                    self.add_directed_server_chat_message(
+                        "No player with this ID exists".to_string(),
+                        admin_player_index,
+                    );
+                }
+            } else {
+                self.admin_deny_message(admin_player_index);
+            }
+        }
+    }
+
+    pub(crate) fn resync(&mut self, admin_player_index: usize, resync_player_index: usize) {
+        if let Some(player) = &self.players[admin_player_index] {
+            if player.is_admin {
+                self.audit_admin_command(admin_player_index, Some(resync_player_index));
+                let admin_player_name = player.player_name.clone();
+                // The next packet sent will be the first one without a baseline
+                let resync_packet = self.game.packet.wrapping_add(1);
+                if let Some(resync_player) = &mut self.players[resync_player_index] {
+                    resync_player.known_packet = u32::MAX;
+                    resync_player.resync_packet = Some(resync_packet);
+                    let resync_player_name = resync_player.player_name.clone();
+
+                    info!(
+                        "{} ({}) reset packet baseline of {} ({})",
+                        admin_player_name,
+                        admin_player_index,
+                        resync_player_name,
+                        resync_player_index
+                    );
+                    let msg = format!("Sending full updates to {}", resync_player_name);
+                    self.add_directed_server_chat_message(msg, admin_player_index);
+                } else {
+                    self.add_directed_server_chat_message(
+                        "No player with this ID exists".to_string(),
+                        admin_player_index,
+                    );
+                }
+            } else {
+                self.admin_deny_message(admin_player_index);
+            }
+        }
+    }
+
     pub(crate) fn clear_bans(&mut self, player_index: usize) {
         if let Some(player) = &self.players[player_index] {
             if player.is_admin {
+                self.audit_admin_command(player_index, None);
                 self.ban_list.clear();
                 info!("{} ({}) cleared bans", player.player_name, player_index);
 
@@ -515,6 +701,45 @@ impl HQMServer {
         }
     }
 
+    pub(crate) fn reload_bans(&mut self, player_index: usize) {
+        if let Some(player) = &self.players[player_index] {
+            if player.is_admin {
+                self.audit_admin_command(player_index, None);
+                let path = match &self.config.bans_file {
+                    Some(path) => path.clone(),
+                    None => {
+                        self.add_directed_server_chat_message(
+                            String::from("No bans file configured"),
+                            player_index,
+                        );
+                        return;
+                    }
+                };
+                match load_bans(&path) {
+                    Ok(ban_list) => {
+                        let count = ban_list.len();
+                        self.ban_list = ban_list;
+                        info!(
+                            "{} ({}) reloaded {} bans from {}",
+                            player.player_name, player_index, count, path
+                        );
+                        let msg = format!("{} bans loaded", count);
+                        self.add_directed_server_chat_message(msg, player_index);
+                    }
+                    Err(e) => {
+                        info!("Could not load bans from {}: {}", path, e);
+                        self.add_directed_server_chat_message(
+                            String::from("Could not read bans file"),
+                            player_index,
+                        );
+                    }
+                }
+            } else {
+                self.admin_deny_message(player_index);
+            }
+        }
+    }
+
     pub(crate) fn set_clock(
         &mut self,
         input_minutes: u32,
@@ -523,6 +748,7 @@ impl HQMServer {
     ) {
         if let Some(player) = &self.players[player_index] {
             if player.is_admin {
+                self.audit_admin_command(player_index, None);
                 self.game.time = (input_minutes * 60 * 100) + (input_seconds * 100);
 
                 info!(
@@ -540,6 +766,7 @@ impl HQMServer {
     pub(crate) fn set_score(&mut self, input_team: HQMTeam, input_score: u32, player_index: usize) {
         if let Some(player) = &self.players[player_index] {
             if player.is_admin {
+                self.audit_admin_command(player_index, None);
                 match input_team {
                     HQMTeam::Red => {
                         self.game.red_score = input_score;
@@ -571,6 +798,7 @@ impl HQMServer {
     pub(crate) fn set_period(&mut self, input_period: u32, player_index: usize) {
         if let Some(player) = &self.players[player_index] {
             if player.is_admin {
+                self.audit_admin_command(player_index, None);
                 self.game.period = input_period;
 
                 info!(
@@ -588,6 +816,7 @@ impl HQMServer {
     pub(crate) fn set_mercy(&mut self, mercy: u32, player_index: usize) {
         if let Some(player) = &self.players[player_index] {
             if player.is_admin {
+                self.audit_admin_command(player_index, None);
                 self.config.mercy_rule = mercy;
 
                 info!(
@@ -606,8 +835,11 @@ impl HQMServer {
         if self.config.mode == HQMServerMode::Match && self.game.state != HQMGameState::GameOver {
             if let Some(player) = &self.players[player_index] {
                 if player.is_admin {
+                    self.audit_admin_command(player_index, None);
                     self.game.time_break = 5 * 100;
+                    self.game.break_reason = HQMBreakReason::Faceoff;
                     self.game.paused = false; // Unpause if it's paused as well
+                    self.game.paused_by_admin = false;
 
                     let msg = format!("Faceoff initiated by {}", player.player_name);
                     info!(
@@ -625,6 +857,7 @@ impl HQMServer {
     pub(crate) fn reset_game(&mut self, player_index: usize) {
         if let Some(player) = &self.players[player_index] {
             if player.is_admin {
+                self.audit_admin_command(player_index, None);
                 info!("{} ({}) reset game", player.player_name, player_index);
                 let msg = format!("Game reset by {}", player.player_name);
 
@@ -640,6 +873,7 @@ impl HQMServer {
     pub(crate) fn start_game(&mut self, player_index: usize) {
         if let Some(player) = &self.players[player_index] {
             if player.is_admin {
+                self.audit_admin_command(player_index, None);
                 if self.config.mode == HQMServerMode::Match
                     && self.game.state == HQMGameState::Warmup
                 {
@@ -659,7 +893,9 @@ impl HQMServer {
     pub(crate) fn pause(&mut self, player_index: usize) {
         if let Some(player) = &self.players[player_index] {
             if player.is_admin {
+                self.audit_admin_command(player_index, None);
                 self.game.paused = true;
+                self.game.paused_by_admin = true;
                 info!("{} ({}) paused game", player.player_name, player_index);
                 let msg = format!("Game paused by {}", player.player_name);
                 self.add_server_chat_message(msg);
@@ -672,7 +908,9 @@ impl HQMServer {
     pub(crate) fn unpause(&mut self, player_index: usize) {
         if let Some(player) = &self.players[player_index] {
             if player.is_admin {
+                self.audit_admin_command(player_index, None);
                 self.game.paused = false;
+                self.game.paused_by_admin = false;
                 info!("{} ({}) resumed game", player.player_name, player_index);
                 let msg = format!("Game resumed by {}", player.player_name);
 
@@ -686,6 +924,7 @@ impl HQMServer {
     pub(crate) fn set_icing_rule(&mut self, player_index: usize, rule: &str) {
         if let Some(player) = &self.players[player_index] {
             if player.is_admin {
+                self.audit_admin_command(player_index, None);
                 match rule {
                     "on" | "touch" => {
                         self.config.icing = HQMIcingConfiguration::Touch;
@@ -725,6 +964,7 @@ impl HQMServer {
     pub(crate) fn set_offside_rule(&mut self, player_index: usize, rule: &str) {
         if let Some(player) = &self.players[player_index] {
             if player.is_admin {
+                self.audit_admin_command(player_index, None);
                 match rule {
                     "on" | "delayed" => {
                         self.config.offside = HQMOffsideConfiguration::Delayed;
@@ -743,14 +983,719 @@ impl HQMServer {
 
                         self.add_server_chat_message(msg);
                     }
-                    "off" => {
-                        self.config.offside = HQMOffsideConfiguration::Off;
-                        info!("{} ({}) disabled offside", player.player_name, player_index);
-                        let msg = format!("Offside disabled by {}", player.player_name);
+                    "off" => {
+                        self.config.offside = HQMOffsideConfiguration::Off;
+                        info!("{} ({}) disabled offside", player.player_name, player_index);
+                        let msg = format!("Offside disabled by {}", player.player_name);
+
+                        self.add_server_chat_message(msg);
+                    }
+                    _ => {}
+                }
+            } else {
+                self.admin_deny_message(player_index);
+            }
+        }
+    }
+
+    pub(crate) fn set_warmup_pucks(&mut self, player_index: usize, pucks: &str) {
+        if let Some(player) = &self.players[player_index] {
+            if player.is_admin {
+                self.audit_admin_command(player_index, None);
+                if let Ok(new_num) = pucks.parse::<usize>() {
+                    if new_num <= 16 {
+                        self.config.warmup_pucks = new_num;
+
+                        info!(
+                            "{} ({}) set warmup pucks to {}",
+                            player.player_name, player_index, new_num
+                        );
+                        let msg = format!(
+                            "Warmup pucks set to {} by {}, applies from next game",
+                            new_num, player.player_name
+                        );
+
+                        self.add_server_chat_message(msg);
+                    }
+                }
+            } else {
+                self.admin_deny_message(player_index);
+            }
+        }
+    }
+
+    pub(crate) fn set_team_size(&mut self, player_index: usize, size: &str) {
+        if let Some(player) = &self.players[player_index] {
+            if player.is_admin {
+                self.audit_admin_command(player_index, None);
+                if let Ok(new_num) = size.parse::<usize>() {
+                    if new_num > 0 && new_num <= 15 {
+                        self.config.team_max = new_num;
+                        self.config.red_max = None;
+                        self.config.blue_max = None;
+                        self.team_max_restore = None;
+
+                        info!(
+                            "{} ({}) set team size to {}",
+                            player.player_name, player_index, new_num
+                        );
+                        let msg = format!("Team size set to {} by {}", new_num, player.player_name);
+
+                        self.add_server_chat_message(msg);
+                    }
+                }
+            } else {
+                self.admin_deny_message(player_index);
+            }
+        }
+    }
+
+    pub(crate) fn set_replay(&mut self, player_index: usize, rule: &str) {
+        if let Some(player) = &self.players[player_index] {
+            if player.is_admin {
+                self.audit_admin_command(player_index, None);
+                match rule {
+                    "on" => {
+                        self.config.replays_enabled = true;
+                        if self.game.replay_data.len() < 64 * 1024 * 1024 {
+                            self.game
+                                .replay_data
+                                .reserve((64 * 1024 * 1024) - self.game.replay_data.len())
+                        }
+
+                        info!("{} ({}) enabled replays", player.player_name, player_index);
+                        let msg = format!("Replays enabled by {}", player.player_name);
+
+                        self.add_server_chat_message(msg);
+                    }
+                    "off" => {
+                        self.config.replays_enabled = false;
+
+                        info!("{} ({}) disabled replays", player.player_name, player_index);
+                        let msg = format!("Replays disabled by {}", player.player_name);
+
+                        self.add_server_chat_message(msg);
+                    }
+                    _ => {}
+                }
+            } else {
+                self.admin_deny_message(player_index);
+            }
+        }
+    }
+
+    pub(crate) fn set_goal_break(&mut self, player_index: usize, rule: &str) {
+        if let Some(player) = &self.players[player_index] {
+            if player.is_admin {
+                self.audit_admin_command(player_index, None);
+                match rule {
+                    "on" => {
+                        self.config.goal_break = true;
+
+                        info!(
+                            "{} ({}) enabled goal break",
+                            player.player_name, player_index
+                        );
+                        let msg = format!("Goal break enabled by {}", player.player_name);
+
+                        self.add_server_chat_message(msg);
+                    }
+                    "off" => {
+                        self.config.goal_break = false;
+
+                        info!(
+                            "{} ({}) disabled goal break",
+                            player.player_name, player_index
+                        );
+                        let msg = format!("Goal break disabled by {}", player.player_name);
+
+                        self.add_server_chat_message(msg);
+                    }
+                    _ => {}
+                }
+            } else {
+                self.admin_deny_message(player_index);
+            }
+        }
+    }
+
+    pub(crate) fn set_public(&mut self, player_index: usize, rule: &str) {
+        if let Some(player) = &self.players[player_index] {
+            if player.is_admin {
+                self.audit_admin_command(player_index, None);
+                match rule {
+                    "on" => {
+                        self.config.public = true;
+                        self.public.store(true, Ordering::Relaxed);
+
+                        info!(
+                            "{} ({}) enabled master server announce",
+                            player.player_name, player_index
+                        );
+                        let msg = String::from("Server is now listed publicly");
+                        self.add_directed_server_chat_message(msg, player_index);
+                    }
+                    "off" => {
+                        self.config.public = false;
+                        self.public.store(false, Ordering::Relaxed);
+
+                        info!(
+                            "{} ({}) disabled master server announce",
+                            player.player_name, player_index
+                        );
+                        let msg = String::from("Server is no longer listed publicly");
+                        self.add_directed_server_chat_message(msg, player_index);
+                    }
+                    _ => {}
+                }
+            } else {
+                self.admin_deny_message(player_index);
+            }
+        }
+    }
+
+    pub(crate) fn set_ranked_login(&mut self, player_index: usize, rule: &str) {
+        if let Some(player) = &self.players[player_index] {
+            if player.is_admin {
+                self.audit_admin_command(player_index, None);
+                match rule {
+                    "open" => {
+                        self.config.ranked_login_open = true;
+
+                        info!(
+                            "{} ({}) opened ranked login",
+                            player.player_name, player_index
+                        );
+                        let msg = format!("Ranked login opened by {}", player.player_name);
+
+                        self.add_server_chat_message(msg);
+                    }
+                    "close" => {
+                        self.config.ranked_login_open = false;
+
+                        info!(
+                            "{} ({}) closed ranked login",
+                            player.player_name, player_index
+                        );
+                        let msg = format!("Ranked login closed by {}", player.player_name);
+
+                        self.add_server_chat_message(msg);
+                    }
+                    _ => {}
+                }
+            } else {
+                self.admin_deny_message(player_index);
+            }
+        }
+    }
+
+    pub(crate) fn set_once_team_size(&mut self, player_index: usize, size: &str) {
+        if let Some(player) = &self.players[player_index] {
+            if player.is_admin {
+                self.audit_admin_command(player_index, None);
+                if let Ok(new_num) = size.parse::<usize>() {
+                    if new_num > 0 && new_num <= 15 {
+                        // Several uses before the faceoff still restore the original value
+                        let (previous, _, _) = *self.team_max_restore.get_or_insert((
+                            self.config.team_max,
+                            self.config.red_max,
+                            self.config.blue_max,
+                        ));
+                        // Per-team limits would otherwise take precedence when joining
+                        self.config.team_max = new_num;
+                        self.config.red_max = None;
+                        self.config.blue_max = None;
+
+                        info!(
+                            "{} ({}) set team size to {} until the next faceoff",
+                            player.player_name, player_index, new_num
+                        );
+                        let msg = format!(
+                            "Team size set to {} by {} for the next faceoff, then back to {}",
+                            new_num, player.player_name, previous
+                        );
+
+                        self.add_server_chat_message(msg);
+                    }
+                }
+            } else {
+                self.admin_deny_message(player_index);
+            }
+        }
+    }
+
+    pub(crate) fn set_team_parity(&mut self, player_index: usize, rule: &str) {
+        if let Some(player) = &self.players[player_index] {
+            if player.is_admin {
+                self.audit_admin_command(player_index, None);
+                match rule {
+                    "on" => {
+                        self.config.force_team_size_parity = true;
+
+                        info!(
+                            "{} ({}) enabled team size parity",
+                            player.player_name, player_index
+                        );
+                        let msg = format!("Team size parity enabled by {}", player.player_name);
+
+                        self.add_server_chat_message(msg);
+                    }
+                    "off" => {
+                        self.config.force_team_size_parity = false;
+
+                        info!(
+                            "{} ({}) disabled team size parity",
+                            player.player_name, player_index
+                        );
+                        let msg = format!("Team size parity disabled by {}", player.player_name);
+
+                        self.add_server_chat_message(msg);
+                    }
+                    _ => {}
+                }
+            } else {
+                self.admin_deny_message(player_index);
+            }
+        }
+    }
+
+    fn cheat_gravity(&mut self, split: &[&str]) {
+        if split.len() >= 2 {
+            let gravity = split[1].parse::<f32>();
+            if let Ok(gravity) = gravity {
+                self.game.world.gravity = gravity / 10000.0;
+            }
+        }
+    }
+
+    fn cheat_mass(&mut self, split: &[&str]) {
+        if split.len() >= 3 {
+            let player = split[1]
+                .parse::<usize>()
+                .ok()
+                .and_then(|x| self.players.get_mut(x).and_then(|x| x.as_mut()));
+            let mass = split[2].parse::<f32>();
+            if let Some(player) = player {
+                if let Ok(mass) = mass {
+                    player.mass = mass;
+                    if let Some(skater_obj_index) = player.skater {
+                        if let HQMGameObject::Player(skater) =
+                            &mut self.game.world.objects[skater_obj_index]
+                        {
+                            for collision_ball in skater.collision_balls.iter_mut() {
+                                collision_ball.mass = mass;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    pub(crate) fn cheat(&mut self, player_index: usize, arg: &str) {
+        if let Some(player) = &self.players[player_index] {
+            if player.is_admin {
+                self.audit_admin_command(player_index, None);
+                let split: Vec<&str> = arg.split_whitespace().collect();
+                if let Some(&command) = split.get(0) {
+                    match command {
+                        "mass" => {
+                            self.cheat_mass(&split);
+                        }
+                        "gravity" => {
+                            self.cheat_gravity(&split);
+                        }
+                        _ => {}
+                    }
+                }
+            } else {
+                self.admin_deny_message(player_index);
+            }
+        }
+    }
+
+    pub(crate) fn perf_detail(&mut self, player_index: usize) {
+        if let Some(player) = &self.players[player_index] {
+            if player.is_admin {
+                self.audit_admin_command(player_index, None);
+                let msg = match self.tick_timings.averages() {
+                    Some([input, simulate, events, send]) => format!(
+                        "In {:.2} Sim {:.2} Ev {:.2} Send {:.2} ms",
+                        input, simulate, events, send
+                    ),
+                    None => "No tick timings recorded yet".to_string(),
+                };
+                self.add_directed_server_chat_message(msg, player_index);
+            } else {
+                self.admin_deny_message(player_index);
+            }
+        }
+    }
+
+    pub(crate) fn capacity(&mut self, player_index: usize) {
+        if let Some(player) = &self.players[player_index] {
+            if player.is_admin {
+                self.audit_admin_command(player_index, None);
+                let players = self.players.iter().flatten().count();
+                let objects = &self.game.world.objects;
+                let used_objects = objects
+                    .iter()
+                    .filter(|x| !matches!(x, HQMGameObject::None))
+                    .count();
+                let saved_ticks = &self.game.saved_ticks;
+                let msgs = vec![
+                    format!("Players {}/{}", players, self.config.player_max),
+                    format!("Objects {}/{}", used_objects, objects.len()),
+                    format!(
+                        "Saved ticks {}/{}",
+                        saved_ticks.len(),
+                        saved_ticks.capacity()
+                    ),
+                ];
+                for msg in msgs {
+                    self.add_directed_server_chat_message(msg, player_index);
+                }
+            } else {
+                self.admin_deny_message(player_index);
+            }
+        }
+    }
+
+    pub(crate) fn set_points(&mut self, player_index: usize, arg: &str) {
+        if let Some(player) = &self.players[player_index] {
+            if player.is_head_admin {
+                self.audit_admin_command(player_index, None);
+                let args: Vec<&str> = arg.split_whitespace().collect();
+                let points = match args.get(1).map(|x| x.parse::<i32>()) {
+                    Some(Ok(points)) if args.len() == 2 => points,
+                    _ => {
+                        self.add_directed_server_chat_message(
+                            String::from("Usage: /setpoints <name> <value>"),
+                            player_index,
+                        );
+                        return;
+                    }
+                };
+                let sender = match &self.msg_sender {
+                    Some(sender) => sender.clone(),
+                    None => return,
+                };
+                let admin_name = player.player_name.clone();
+                let login = args[0].to_string();
+
+                tokio::spawn(async move {
+                    let update_login = login.clone();
+                    let result = tokio::task::spawn_blocking(move || {
+                        Self::set_player_points(&update_login, points)
+                    })
+                    .await;
+                    let msg = match result {
+                        Ok(Ok(previous)) => {
+                            info!(
+                                "{} ({}) set points of {} from {} to {}",
+                                admin_name, player_index, login, previous, points
+                            );
+                            format!("Points of {} set from {} to {}", login, previous, points)
+                        }
+                        Ok(Err(e)) => {
+                            info!("Could not set points of {}: {}", login, e);
+                            format!("Could not set points of {}", login)
+                        }
+                        Err(_) => format!("Could not set points of {}", login),
+                    };
+                    let _ = sender
+                        .send(HQMServerReceivedData::DirectedServerChat {
+                            player_index,
+                            player_name: admin_name,
+                            messages: vec![msg],
+                        })
+                        .await;
+                });
+            } else {
+                self.admin_deny_message(player_index);
+            }
+        }
+    }
+
+    pub(crate) fn my_best(&mut self, player_index: usize, arg: &str) {
+        if let Some(player) = &self.players[player_index] {
+            // Same numbering as /vote
+            let game = match arg.trim().parse::<usize>() {
+                Ok(game) if game >= 1 && game <= 7 => game - 1,
+                _ => {
+                    self.add_directed_server_chat_message(
+                        String::from("Usage: /mybest <1-7>"),
+                        player_index,
+                    );
+                    return;
+                }
+            };
+            let sender = match &self.msg_sender {
+                Some(sender) => sender.clone(),
+                None => return,
+            };
+            let player_name = player.player_name.clone();
+            let login = player_name.clone();
+
+            tokio::spawn(async move {
+                let result = tokio::task::spawn_blocking(move || {
+                    Self::get_player_mini_game_best(&login, game)
+                })
+                .await;
+                let name = Self::mini_game_name(game);
+                let msg = match result {
+                    Ok(Ok(Some(best))) => format!("Your best in {}: {}", name, best),
+                    Ok(Ok(None)) => format!("No results in {} yet", name),
+                    Ok(Err(e)) => {
+                        info!("Could not load best result of {}: {}", player_name, e);
+                        String::from("Could not load your best result")
+                    }
+                    Err(_) => String::from("Could not load your best result"),
+                };
+                let _ = sender
+                    .send(HQMServerReceivedData::DirectedServerChat {
+                        player_index,
+                        player_name,
+                        messages: vec![msg],
+                    })
+                    .await;
+            });
+        }
+    }
+
+    pub(crate) fn export_teams(&mut self, player_index: usize) {
+        if let Some(player) = &self.players[player_index] {
+            if player.is_admin {
+                self.audit_admin_command(player_index, None);
+                let mut red_players = vec![];
+                let mut blue_players = vec![];
+                for object in self.game.world.objects.iter() {
+                    if let HQMGameObject::Player(skater) = object {
+                        if let Some(skater_player) = &self.players[skater.connected_player_index] {
+                            let name = skater_player.player_name.clone();
+                            match skater.team {
+                                HQMTeam::Red => red_players.push(name),
+                                HQMTeam::Blue => blue_players.push(name),
+                            }
+                        }
+                    }
+                }
+
+                let teams = format!(
+                    "RED: {} | BLU: {}",
+                    red_players.join(","),
+                    blue_players.join(",")
+                );
+                info!(
+                    "{} ({}) exported teams: {}",
+                    player.player_name, player_index, teams
+                );
+                for msg in split_chat_message(&teams) {
+                    self.add_directed_server_chat_message(msg, player_index);
+                }
+            } else {
+                self.admin_deny_message(player_index);
+            }
+        }
+    }
+
+    pub(crate) fn eligible(&mut self, player_index: usize) {
+        if let Some(player) = &self.players[player_index] {
+            if player.is_admin {
+                self.audit_admin_command(player_index, None);
+                let sender = match &self.msg_sender {
+                    Some(sender) => sender.clone(),
+                    None => return,
+                };
+                let admin_name = player.player_name.clone();
+                let min_points = self.config.ranked_min_points;
+                let names: Vec<String> = self
+                    .players
+                    .iter()
+                    .flatten()
+                    .map(|p| p.player_name.clone())
+                    .collect();
+
+                tokio::spawn(async move {
+                    let lookups: Vec<_> = names
+                        .into_iter()
+                        .map(|name| {
+                            tokio::task::spawn_blocking(move || {
+                                let points = Self::get_player_season_points(&name);
+                                (name, points)
+                            })
+                        })
+                        .collect();
+
+                    let mut eligible = vec![];
+                    let mut failed = 0;
+                    for lookup in lookups {
+                        match lookup.await {
+                            Ok((name, Ok(points))) => {
+                                if points >= min_points as i64 {
+                                    eligible.push(format!("{} ({})", name, points));
+                                }
+                            }
+                            Ok((name, Err(e))) => {
+                                warn!("Points lookup for {} failed: {}", name, e);
+                                failed += 1;
+                            }
+                            Err(e) => {
+                                warn!("Points lookup failed: {}", e);
+                                failed += 1;
+                            }
+                        }
+                    }
+
+                    let mut messages = vec![format!(
+                        "{} players with at least {} points",
+                        eligible.len(),
+                        min_points
+                    )];
+                    messages.extend(chat_lines(&eligible));
+                    if failed > 0 {
+                        messages.push(format!("Could not look up {} players", failed));
+                    }
+                    let _ = sender
+                        .send(HQMServerReceivedData::DirectedServerChat {
+                            player_index,
+                            player_name: admin_name,
+                            messages,
+                        })
+                        .await;
+                });
+            } else {
+                self.admin_deny_message(player_index);
+            }
+        }
+    }
+
+    pub(crate) fn next_mini_game_player(&mut self, player_index: usize) {
+        if let Some(player) = &self.players[player_index] {
+            if player.is_admin {
+                self.audit_admin_command(player_index, None);
+                if self.game.period == 0
+                    && !self.game.ranked_started
+                    && self.game.logged_players.len() != 0
+                    && self.game.time > 0
+                {
+                    let admin_name = player.player_name.clone();
+                    self.game.mini_game_time = 0;
+                    self.game.mini_game_warmup = 500;
+
+                    info!(
+                        "{} ({}) skipped mini-game attempt of {}",
+                        admin_name, player_index, self.game.next_game_player
+                    );
+
+                    // The warmup countdown takes this pick instead of drawing another player
+                    self.game.picked_mini_game_player = None;
+                    let next = self.get_random_logged_player();
+                    let next_name = self
+                        .game
+                        .logged_players
+                        .iter()
+                        .find(|p| p.player_i == next)
+                        .map(|p| p.player_name.clone());
+                    let msg = match next_name {
+                        Some(name) => {
+                            self.game.picked_mini_game_player = Some(next);
+                            format!("Attempt skipped by {}, next try by {}", admin_name, name)
+                        }
+                        None => format!("Attempt skipped by {}, no player available", admin_name),
+                    };
+                    self.add_server_chat_message(msg);
+                } else {
+                    self.add_directed_server_chat_message(
+                        "No mini-game in progress".to_string(),
+                        player_index,
+                    );
+                }
+            } else {
+                self.admin_deny_message(player_index);
+            }
+        }
+    }
+
+    pub(crate) fn reset_mini_game(&mut self, player_index: usize) {
+        if let Some(player) = &self.players[player_index] {
+            if player.is_admin {
+                self.audit_admin_command(player_index, None);
+                if self.game.period == 0 && !self.game.ranked_started && self.game.time > 0 {
+                    self.game.pucks_in_net = vec![];
+                    self.game.gk_catches = 0;
+                    self.game.gk_speed = self.config.goalie_start_speed;
+                    self.game.gk_last_vector = 2;
+                    self.game.gk_last_height = 2;
+                    self.game.gk_puck_in_net = false;
+                    self.game.last_puck_point = 0.0;
+                    // Restarting the warmup countdown sets up a new world and picks a new player
+                    self.game.mini_game_time = 0;
+                    self.game.mini_game_warmup = 500;
+
+                    info!(
+                        "{} ({}) reset mini-game {}",
+                        player.player_name,
+                        player_index,
+                        Self::mini_game_name(self.game.last_mini_game)
+                    );
+                    let msg = format!("Mini-game reset by {}", player.player_name);
+                    self.add_server_chat_message(msg);
+                } else {
+                    self.add_directed_server_chat_message(
+                        "No mini-game in progress".to_string(),
+                        player_index,
+                    );
+                }
+            } else {
+                self.admin_deny_message(player_index);
+            }
+        }
+    }
+
+    pub(crate) fn force_start_ranked(&mut self, player_index: usize) {
+        if let Some(player) = &self.players[player_index] {
+            if player.is_admin {
+                self.audit_admin_command(player_index, None);
+                let logged = self.game.logged_players.len();
+                if self.game.ranked_started || self.game.period != 0 {
+                    self.add_directed_server_chat_message(
+                        String::from("Ranked game already started"),
+                        player_index,
+                    );
+                } else if logged < 2 {
+                    self.add_directed_server_chat_message(
+                        String::from("At least 2 logged in players are needed"),
+                        player_index,
+                    );
+                } else {
+                    let admin_name = player.player_name.clone();
+
+                    // Teams must be even, the last player to log in waits for the next game
+                    let count = logged - logged % 2;
+                    if count < logged {
+                        let extra = self.game.logged_players.remove(count);
+                        let msg = format!(
+                            "{} will play in the next ranked game to keep the teams even",
+                            extra.player_name
+                        );
+                        self.game.logged_players_for_next.insert(0, extra);
+                        self.add_server_chat_message(msg);
+                    }
 
-                        self.add_server_chat_message(msg);
-                    }
-                    _ => {}
+                    // Only lasts for this game, new games use the configured ranked count
+                    self.game.ranked_count = count;
+
+                    info!(
+                        "{} ({}) started ranked game with {} players",
+                        admin_name, player_index, count
+                    );
+                    let msg = format!(
+                        "Ranked game with {} players started by {}",
+                        count, admin_name
+                    );
+                    self.add_server_chat_message(msg);
+                    self.start_ranked_game();
                 }
             } else {
                 self.admin_deny_message(player_index);
@@ -758,20 +1703,34 @@ impl HQMServer {
         }
     }
 
-    pub(crate) fn set_team_size(&mut self, player_index: usize, size: &str) {
+    pub(crate) fn schedule(&mut self, player_index: usize, arg: &str) {
         if let Some(player) = &self.players[player_index] {
             if player.is_admin {
-                if let Ok(new_num) = size.parse::<usize>() {
-                    if new_num > 0 && new_num <= 15 {
-                        self.config.team_max = new_num;
-
+                self.audit_admin_command(player_index, None);
+                let (seconds, text) = match arg.find(' ') {
+                    Some(i) => (&arg[..i], arg[i + 1..].trim()),
+                    None => (arg, ""),
+                };
+                // Intervals are kept in ticks, seconds that overflow them are rejected as invalid
+                let parsed = seconds.parse::<u32>().ok().and_then(|seconds| {
+                    seconds.checked_mul(100).map(|interval| (seconds, interval))
+                });
+                match parsed {
+                    Some((seconds, interval)) if seconds > 0 && !text.is_empty() => {
                         info!(
-                            "{} ({}) set team size to {}",
-                            player.player_name, player_index, new_num
+                            "{} ({}) scheduled message every {} seconds: {}",
+                            player.player_name, player_index, seconds, text
+                        );
+                        self.scheduled_messages
+                            .push((interval, text.to_string(), interval));
+                        let msg = format!("Message scheduled every {} seconds", seconds);
+                        self.add_directed_server_chat_message(msg, player_index);
+                    }
+                    _ => {
+                        self.add_directed_server_chat_message(
+                            String::from("Usage: /schedule <seconds> <message>"),
+                            player_index,
                         );
-                        let msg = format!("Team size set to {} by {}", new_num, player.player_name);
-
-                        self.add_server_chat_message(msg);
                     }
                 }
             } else {
@@ -780,32 +1739,53 @@ impl HQMServer {
         }
     }
 
-    pub(crate) fn set_replay(&mut self, player_index: usize, rule: &str) {
+    pub(crate) fn unschedule(&mut self, player_index: usize) {
         if let Some(player) = &self.players[player_index] {
             if player.is_admin {
-                match rule {
-                    "on" => {
-                        self.config.replays_enabled = true;
-                        if self.game.replay_data.len() < 64 * 1024 * 1024 {
-                            self.game
-                                .replay_data
-                                .reserve((64 * 1024 * 1024) - self.game.replay_data.len())
-                        }
-
-                        info!("{} ({}) enabled replays", player.player_name, player_index);
-                        let msg = format!("Replays enabled by {}", player.player_name);
-
-                        self.add_server_chat_message(msg);
-                    }
-                    "off" => {
-                        self.config.replays_enabled = false;
-
-                        info!("{} ({}) disabled replays", player.player_name, player_index);
-                        let msg = format!("Replays disabled by {}", player.player_name);
+                self.audit_admin_command(player_index, None);
+                info!(
+                    "{} ({}) cleared {} scheduled messages",
+                    player.player_name,
+                    player_index,
+                    self.scheduled_messages.len()
+                );
+                self.scheduled_messages.clear();
+                self.add_directed_server_chat_message(
+                    String::from("Scheduled messages cleared"),
+                    player_index,
+                );
+            } else {
+                self.admin_deny_message(player_index);
+            }
+        }
+    }
 
-                        self.add_server_chat_message(msg);
-                    }
-                    _ => {}
+    pub(crate) fn versions(&mut self, player_index: usize) {
+        if let Some(player) = &self.players[player_index] {
+            if player.is_admin {
+                self.audit_admin_command(player_index, None);
+                let accepted = self
+                    .config
+                    .accepted_versions
+                    .iter()
+                    .map(|version| version.to_string())
+                    .collect::<Vec<_>>();
+                let mut rejected = self.rejected_versions.iter().collect::<Vec<_>>();
+                rejected.sort();
+                let rejected = rejected
+                    .iter()
+                    .map(|(version, count)| format!("{} ({})", version, count))
+                    .collect::<Vec<_>>();
+
+                let mut msgs = vec![format!("Accepted versions: {}", accepted.join(", "))];
+                if rejected.is_empty() {
+                    msgs.push(String::from("No rejected join requests"));
+                } else {
+                    msgs.push(String::from("Rejected versions (requests):"));
+                    msgs.extend(chat_lines(&rejected));
+                }
+                for msg in msgs {
+                    self.add_directed_server_chat_message(msg, player_index);
                 }
             } else {
                 self.admin_deny_message(player_index);
@@ -813,33 +1793,30 @@ impl HQMServer {
         }
     }
 
-    pub(crate) fn set_team_parity(&mut self, player_index: usize, rule: &str) {
+    pub(crate) fn listen(&mut self, player_index: usize) {
         if let Some(player) = &self.players[player_index] {
             if player.is_admin {
-                match rule {
-                    "on" => {
-                        self.config.force_team_size_parity = true;
-
-                        info!(
-                            "{} ({}) enabled team size parity",
-                            player.player_name, player_index
-                        );
-                        let msg = format!("Team size parity enabled by {}", player.player_name);
-
-                        self.add_server_chat_message(msg);
-                    }
-                    "off" => {
-                        self.config.force_team_size_parity = false;
-
-                        info!(
-                            "{} ({}) disabled team size parity",
-                            player.player_name, player_index
-                        );
-                        let msg = format!("Team size parity disabled by {}", player.player_name);
+                self.audit_admin_command(player_index, None);
+                let msg = match self.local_addr {
+                    Some(addr) => format!(
+                        "Listening at {}, configured port {}",
+                        addr, self.config.port
+                    ),
+                    None => format!("Not bound yet, configured port {}", self.config.port),
+                };
+                self.add_directed_server_chat_message(msg, player_index);
+            } else {
+                self.admin_deny_message(player_index);
+            }
+        }
+    }
 
-                        self.add_server_chat_message(msg);
-                    }
-                    _ => {}
+    pub(crate) fn config_all(&mut self, player_index: usize) {
+        if let Some(player) = &self.players[player_index] {
+            if player.is_admin {
+                self.audit_admin_command(player_index, None);
+                for msg in chat_lines(&self.config.entries()) {
+                    self.add_directed_server_chat_message(msg, player_index);
                 }
             } else {
                 self.admin_deny_message(player_index);
@@ -847,53 +1824,81 @@ impl HQMServer {
         }
     }
 
-    fn cheat_gravity(&mut self, split: &[&str]) {
-        if split.len() >= 2 {
-            let gravity = split[1].parse::<f32>();
-            if let Ok(gravity) = gravity {
-                self.game.world.gravity = gravity / 10000.0;
+    pub(crate) fn replay_dir(&mut self, player_index: usize) {
+        if let Some(player) = &self.players[player_index] {
+            if player.is_admin {
+                self.audit_admin_command(player_index, None);
+                let msgs = vec![
+                    format!("Replay directory: {}", self.config.replay_dir),
+                    format!(
+                        "File names: {}.YYYY-MM-DDTHHMMSS.hrp (game start, UTC)",
+                        self.config.server_name
+                    ),
+                ];
+                for msg in msgs {
+                    self.add_directed_server_chat_message(msg, player_index);
+                }
+            } else {
+                self.admin_deny_message(player_index);
             }
         }
     }
 
-    fn cheat_mass(&mut self, split: &[&str]) {
-        if split.len() >= 3 {
-            let player = split[1]
-                .parse::<usize>()
-                .ok()
-                .and_then(|x| self.players.get_mut(x).and_then(|x| x.as_mut()));
-            let mass = split[2].parse::<f32>();
-            if let Some(player) = player {
-                if let Ok(mass) = mass {
-                    player.mass = mass;
-                    if let Some(skater_obj_index) = player.skater {
-                        if let HQMGameObject::Player(skater) =
-                            &mut self.game.world.objects[skater_obj_index]
-                        {
-                            for collision_ball in skater.collision_balls.iter_mut() {
-                                collision_ball.mass = mass;
-                            }
-                        }
+    pub(crate) fn pucks(&mut self, player_index: usize) {
+        if let Some(player) = &self.players[player_index] {
+            if player.is_admin {
+                self.audit_admin_command(player_index, None);
+                let mut msgs = vec![];
+                for object in self.game.world.objects.iter() {
+                    if let HQMGameObject::Puck(puck) = object {
+                        let pos = &puck.body.pos;
+                        // Velocity is stored per tick
+                        let speed = puck.body.linear_velocity.norm() * 100.0;
+                        msgs.push(format!(
+                            "#{} ({:.2}, {:.2}, {:.2}) {:.1} m/s",
+                            puck.index, pos.x, pos.y, pos.z, speed
+                        ));
                     }
                 }
+                if msgs.is_empty() {
+                    msgs.push(String::from("No pucks"));
+                }
+                for msg in msgs {
+                    self.add_directed_server_chat_message(msg, player_index);
+                }
+            } else {
+                self.admin_deny_message(player_index);
             }
         }
     }
 
-    pub(crate) fn cheat(&mut self, player_index: usize, arg: &str) {
+    pub(crate) fn lines(&mut self, player_index: usize) {
         if let Some(player) = &self.players[player_index] {
             if player.is_admin {
-                let split: Vec<&str> = arg.split_whitespace().collect();
-                if let Some(&command) = split.get(0) {
-                    match command {
-                        "mass" => {
-                            self.cheat_mass(&split);
-                        }
-                        "gravity" => {
-                            self.cheat_gravity(&split);
-                        }
-                        _ => {}
-                    }
+                self.audit_admin_command(player_index, None);
+                let rink = &self.game.world.rink;
+                let mut msgs = vec![];
+                for (name, lines) in [
+                    ("RED", &rink.red_lines_and_net),
+                    ("BLU", &rink.blue_lines_and_net),
+                ]
+                .iter()
+                {
+                    msgs.push(format!(
+                        "{} off {:.2} mid {:.2} def {:.2} goal {:.2}",
+                        name,
+                        lines.offensive_line.point.z,
+                        lines.mid_line.point.z,
+                        lines.defensive_line.point.z,
+                        lines.net.left_post.z
+                    ));
+                }
+                msgs.push(format!(
+                    "Line width {:.2}, rink {:.2} x {:.2}",
+                    rink.red_lines_and_net.offensive_line.width, rink.width, rink.length
+                ));
+                for msg in msgs {
+                    self.add_directed_server_chat_message(msg, player_index);
                 }
             } else {
                 self.admin_deny_message(player_index);
@@ -901,8 +1906,81 @@ impl HQMServer {
         }
     }
 
+    fn start_ranked_game(&mut self) {
+        self.game.ranked_started = true;
+        self.game.time = 2000;
+        self.game.paused = false;
+        self.game.world.gravity = 0.000680555;
+        let sum = self.randomize_players();
+        self.force_players_off_ice_by_system();
+        self.set_teams_by_server(sum);
+    }
+
+    // 0 for red, 1 for blue, None for a draw. A tied game is decided by the shootout goals.
+    fn ranked_winner(&self) -> Option<usize> {
+        let (red, blue) = if self.game.red_score != self.game.blue_score {
+            (self.game.red_score as usize, self.game.blue_score as usize)
+        } else {
+            let goals = |score: &[String]| score.iter().filter(|x| x.as_str() == "+").count();
+            (
+                goals(&self.game.shootout_red_score),
+                goals(&self.game.shootout_blue_score),
+            )
+        };
+        if red > blue {
+            Some(0)
+        } else if blue > red {
+            Some(1)
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn award_ranked_points(&mut self) {
+        self.game.data_saved = true;
+        let winner = self.ranked_winner();
+        let results: Vec<(String, i32)> = self
+            .game
+            .game_players
+            .iter()
+            .map(|p| {
+                let points = match winner {
+                    Some(team) if team == p.player_team => self.config.ranked_win_points as i32,
+                    Some(_) => -(self.config.ranked_loss_points as i32),
+                    None => 0,
+                };
+                let penalty = if p.left_game {
+                    self.config.leave_penalty as i32
+                } else {
+                    0
+                };
+                (p.player_name_r.clone(), points - penalty)
+            })
+            .collect();
+        if results.is_empty() {
+            return;
+        }
+
+        let game_id = self.game.game_id;
+        match winner {
+            Some(0) => self.add_server_chat_message(String::from("Red team won")),
+            Some(_) => self.add_server_chat_message(String::from("Blue team won")),
+            None => self.add_server_chat_message(String::from("Draw, no points awarded")),
+        }
+        tokio::spawn(async move {
+            let result =
+                tokio::task::spawn_blocking(move || Self::save_ranked_points(&results)).await;
+            match result {
+                Ok(Ok(())) => info!("Ranked points of game {} saved", game_id),
+                Ok(Err(e)) => info!("Could not save ranked points of game {}: {}", game_id, e),
+                Err(_) => info!("Could not save ranked points of game {}", game_id),
+            }
+        });
+    }
+
     pub(crate) fn user_logged_in(&mut self, user: &str, next: bool) {
         if next == false {
+            self.game.ranked_stall_ticks = 0;
             let msg = format!(
                 "{} logged in [{}]",
                 user,
@@ -911,13 +1989,7 @@ impl HQMServer {
 
             self.add_server_chat_message(msg);
             if self.game.logged_players.len().to_string() == self.game.ranked_count.to_string() {
-                self.game.ranked_started = true;
-                self.game.time = 2000;
-                self.game.paused = false;
-                self.game.world.gravity = 0.000680555;
-                let sum = self.randomize_players();
-                self.force_players_off_ice_by_system();
-                self.set_teams_by_server(sum);
+                self.start_ranked_game();
             } else {
                 if self.game.logged_players.len() == 1 {
                     self.game.time = 0;
@@ -959,6 +2031,8 @@ impl HQMServer {
                     goals: _,
                     assists: _,
                     leaved_seconds: _,
+                    leave_exempt: _,
+                    left_game: _,
                 } => {
                     if red_count == self.game.ranked_count / 2 {
                         blue_team.push(player_i_r.to_owned());
@@ -1004,10 +2078,35 @@ impl HQMServer {
             self.set_team(i.to_owned(), Some(HQMTeam::Blue));
         }
 
+        let (sum_red, sum_blue) = self.team_points_sums();
         let msg2 = format!("{} {}", sum_red, sum_blue);
         self.add_server_chat_message(msg2);
     }
 
+    // Season points of the red and blue ranked teams
+    pub(crate) fn team_points_sums(&self) -> (usize, usize) {
+        let mut sum_red = 0;
+        let mut sum_blue = 0;
+        for p in self.game.game_players.iter() {
+            if p.player_team == 0 {
+                sum_red += p.player_points;
+            } else {
+                sum_blue += p.player_points;
+            }
+        }
+        (sum_red, sum_blue)
+    }
+
+    pub(crate) fn team_points(&mut self, player_index: usize) {
+        let msg = if self.game.ranked_started {
+            let (sum_red, sum_blue) = self.team_points_sums();
+            format!("Team points: Red {}, Blue {}", sum_red, sum_blue)
+        } else {
+            String::from("No ranked game in progress")
+        };
+        self.add_directed_server_chat_message(msg, player_index);
+    }
+
     pub(crate) fn randomize_players(&mut self) -> usize {
         self.add_server_chat_message(String::from("Ranked game starting"));
         let mut sum = 0;
@@ -1022,6 +2121,8 @@ impl HQMServer {
                 goals: 0,
                 assists: 0,
                 leaved_seconds: 120,
+                leave_exempt: false,
+                left_game: false,
             };
 
             sum = sum + points;
@@ -1049,6 +2150,114 @@ impl HQMServer {
         return score as usize;
     }
 
+    // Total score of the player in the current season
+    pub fn get_player_season_points(login: &str) -> Result<i64, String> {
+        let conn = Self::get_connection();
+
+        let stmt = conn
+            .prepare(
+                "select COALESCE(sum(\"Score\"),0) from public.\"GameStats\" where \"GameId\" in (select \"Id\" from public.\"Stats\" where \"Season\"=(select max(\"Season\") from public.\"Stats\"))
+            and \"Player\" = (select \"Id\" from public.\"Users\" where \"Login\" = $1)",
+            )
+            .map_err(|e| e.to_string())?;
+        let mut points: i64 = 0;
+        for row in stmt.query(&[&login]).map_err(|e| e.to_string())? {
+            points = row.get(0);
+        }
+
+        Ok(points)
+    }
+
+    // Adds a correction to the current season so the player's total becomes the given value,
+    // returns the previous total
+    pub fn set_player_points(login: &str, points: i32) -> Result<i64, String> {
+        let conn = Self::get_connection();
+
+        let stmt = conn
+            .prepare("select count(*) from public.\"Users\" where \"Login\" = $1")
+            .map_err(|e| e.to_string())?;
+        let mut users: i64 = 0;
+        for row in stmt.query(&[&login]).map_err(|e| e.to_string())? {
+            users = row.get(0);
+        }
+        if users == 0 {
+            return Err(format!("Unknown player {}", login));
+        }
+
+        let current = Self::get_player_season_points(login)?;
+        let correction = i32::try_from(i64::from(points) - current)
+            .map_err(|_| format!("Correction from {} to {} is too large", current, points))?;
+
+        // The correction gets a Stats row of its own so no played game's stats change
+        let transaction = conn.transaction().map_err(|e| e.to_string())?;
+        let mut game_id: Option<i32> = None;
+        {
+            let stmt = transaction
+                .prepare("INSERT INTO public.\"Stats\"(\"Season\") VALUES ((select max(\"Season\") from public.\"Stats\")) RETURNING \"Id\"")
+                .map_err(|e| e.to_string())?;
+            for row in stmt.query(&[]).map_err(|e| e.to_string())? {
+                game_id = Some(row.get(0));
+            }
+        }
+        let game_id = game_id.ok_or_else(|| String::from("No adjustment row created"))?;
+        transaction
+            .execute(
+                "INSERT INTO public.\"GameStats\"(\"GameId\", \"Player\", \"Score\") VALUES ($1, (select \"Id\" from public.\"Users\" where \"Login\" = $2), $3);",
+                &[&game_id, &login, &correction],
+            )
+            .map_err(|e| e.to_string())?;
+        transaction.commit().map_err(|e| e.to_string())?;
+
+        Ok(current)
+    }
+
+    // The best result of a player in a mini-game (0-6), speed shots are timed so lower is better
+    pub fn get_player_mini_game_best(login: &str, game: usize) -> Result<Option<String>, String> {
+        let conn = Self::get_connection();
+
+        let order = if game == 0 { "asc" } else { "desc" };
+        let str_sql = format!(
+            "select CAST(r.\"Value\" AS TEXT) from public.\"Results\" r, public.\"Users\" u where u.\"Id\" = r.\"UserId\" and r.\"GameType\"={} and u.\"Login\" = $1 order by r.\"Value\" {} limit 1",
+            game + 1,
+            order
+        );
+        let stmt = conn.prepare(&str_sql).map_err(|e| e.to_string())?;
+        let mut best = None;
+        for row in stmt.query(&[&login]).map_err(|e| e.to_string())? {
+            best = Some(row.get(0));
+        }
+
+        Ok(best)
+    }
+
+    // Adds a game to the current season with a score row for every player
+    pub fn save_ranked_points(results: &[(String, i32)]) -> Result<(), String> {
+        let conn = Self::get_connection();
+        let transaction = conn.transaction().map_err(|e| e.to_string())?;
+
+        let mut game_id: Option<i32> = None;
+        {
+            let stmt = transaction
+                .prepare("INSERT INTO public.\"Stats\"(\"Season\") VALUES ((select max(\"Season\") from public.\"Stats\")) RETURNING \"Id\"")
+                .map_err(|e| e.to_string())?;
+            for row in stmt.query(&[]).map_err(|e| e.to_string())? {
+                game_id = Some(row.get(0));
+            }
+        }
+        let game_id = game_id.ok_or_else(|| String::from("No game row created"))?;
+
+        for (login, score) in results {
+            transaction
+                .execute(
+                    "INSERT INTO public.\"GameStats\"(\"GameId\", \"Player\", \"Score\") VALUES ($1, (select \"Id\" from public.\"Users\" where \"Login\" = $2), $3);",
+                    &[&game_id, login, score],
+                )
+                .map_err(|e| e.to_string())?;
+        }
+
+        transaction.commit().map_err(|e| e.to_string())
+    }
+
     pub fn save_mini_game_result(name: &String, result: String) {
         let conn = Self::get_connection();
 
@@ -1414,6 +2623,12 @@ impl HQMServer {
     }
 
     pub(crate) fn login(&mut self, player_index: usize, password_user: &str) {
+        if !self.config.ranked_login_open {
+            let msg = String::from("Ranked login is closed right now");
+            self.add_directed_server_chat_message(msg, player_index);
+            return;
+        }
+
         let mut logged = false;
         if let Some(player) = &self.players[player_index] {
             for player_item in self.game.logged_players.iter() {
@@ -1505,6 +2720,7 @@ impl HQMServer {
         let puck_line_start = self.game.world.rink.width / 2.0 - 0.4 * ((10 - 1) as f32);
 
         self.game.world.puck_slots = puck_count;
+        self.game.mini_game_stall_ticks.clear();
         for i in 0..puck_count {
             let pos = Point3::new(
                 puck_line_start + 0.8 * (i as f32),
@@ -1518,13 +2734,34 @@ impl HQMServer {
         }
     }
 
-    pub(crate) fn get_random_logged_player(&mut self) -> usize {
+    // Logged players that can take a mini-game attempt
+    fn mini_game_candidates(&self) -> Vec<usize> {
         let mut players: Vec<usize> = vec![];
         for player in self.game.logged_players.iter() {
-            if !player.afk {
+            if player.afk {
+                continue;
+            }
+            // Skip logged players that have disconnected or whose slot was taken by someone else
+            let connected = match self.players.get(player.player_i) {
+                Some(Some(p)) => p.player_name == player.player_name,
+                _ => false,
+            };
+            if connected {
                 players.push(player.player_i);
             }
         }
+        players
+    }
+
+    pub(crate) fn get_random_logged_player(&mut self) -> usize {
+        let players = self.mini_game_candidates();
+
+        // A player announced by /nextplayer may have left or gone AFK since
+        if let Some(index) = self.game.picked_mini_game_player.take() {
+            if players.contains(&index) {
+                return index;
+            }
+        }
 
         let mut non_prev = false;
         let mut index = 0;
@@ -1584,6 +2821,7 @@ impl HQMServer {
         self.game.voted4 = vec![];
         self.game.voted5 = vec![];
         self.game.voted6 = vec![];
+        self.game.voted7 = vec![];
         match self.game.last_mini_game {
             0 => {}
             1 => {}
@@ -1596,50 +2834,95 @@ impl HQMServer {
         }
     }
 
-    pub(crate) fn get_next_mini_game(&mut self) {
-        let mut max_votes = 0;
-        let mut max_votes_game = 0;
-
-        if self.game.voted1.len() > max_votes {
-            max_votes_game = 0;
-            max_votes = self.game.voted1.len();
-        }
-
-        if self.game.voted2.len() > max_votes {
-            max_votes_game = 1;
-            max_votes = self.game.voted2.len();
-        }
-
-        if self.game.voted3.len() > max_votes {
-            max_votes_game = 2;
-            max_votes = self.game.voted3.len();
+    pub(crate) fn mini_game_name(game: usize) -> &'static str {
+        match game {
+            0 => "Speed shots",
+            1 => "Goalkeeper",
+            2 => "Air goals",
+            3 => "Air puck",
+            4 => "Scorer",
+            5 => "Precision",
+            6 => "Passes",
+            _ => "Unknown",
         }
+    }
 
-        if self.game.voted4.len() > max_votes {
-            max_votes_game = 3;
-            max_votes = self.game.voted4.len();
-        }
+    // Returns the mini-game with the most votes and its vote count, ties go to the lower number
+    pub(crate) fn leading_mini_game(&self) -> Option<(usize, usize)> {
+        let votes = [
+            self.game.voted1.len(),
+            self.game.voted2.len(),
+            self.game.voted3.len(),
+            self.game.voted4.len(),
+            self.game.voted5.len(),
+            self.game.voted6.len(),
+            self.game.voted7.len(),
+        ];
 
-        if self.game.voted5.len() > max_votes {
-            max_votes_game = 4;
-            max_votes = self.game.voted5.len();
+        let mut max_votes = 0;
+        let mut max_votes_game = 0;
+        for (game, &count) in votes.iter().enumerate() {
+            if count > max_votes {
+                max_votes_game = game;
+                max_votes = count;
+            }
         }
 
-        if self.game.voted6.len() > max_votes {
-            max_votes_game = 5;
-            max_votes = self.game.voted6.len();
+        if max_votes == 0 {
+            None
+        } else {
+            Some((max_votes_game, max_votes))
         }
+    }
 
-        if self.game.voted7.len() > max_votes {
-            max_votes_game = 6;
-            max_votes = self.game.voted7.len();
-        }
+    pub(crate) fn next_mini_game(&mut self, player_index: usize) {
+        let msg = if self.game.ranked_started || self.game.period != 0 {
+            String::from("No mini-games during a ranked game")
+        } else if self.game.time == 0 && self.game.time_break <= 700 {
+            // get_next_mini_game has already picked the winner of the vote
+            format!(
+                "Next mini-game: {}",
+                Self::mini_game_name(self.game.last_mini_game)
+            )
+        } else {
+            match self.leading_mini_game() {
+                Some((game, votes)) => format!(
+                    "Next mini-game: {} ({} votes so far)",
+                    Self::mini_game_name(game),
+                    votes
+                ),
+                None => String::from("No votes yet, next mini-game will be random"),
+            }
+        };
+        self.add_directed_server_chat_message(msg, player_index);
+    }
 
-        if max_votes == 0 {
-            max_votes_game = rand::thread_rng().gen_range(0, 6);
-        }
+    pub(crate) fn vote_status(&mut self, player_index: usize) {
+        let total = self.game.voted1.len()
+            + self.game.voted2.len()
+            + self.game.voted3.len()
+            + self.game.voted4.len()
+            + self.game.voted5.len()
+            + self.game.voted6.len()
+            + self.game.voted7.len();
+
+        let msg = match self.leading_mini_game() {
+            Some((game, votes)) => format!(
+                "{} votes cast, leading: {} ({} votes)",
+                total,
+                Self::mini_game_name(game),
+                votes
+            ),
+            None => String::from("No votes cast yet"),
+        };
+        self.add_directed_server_chat_message(msg, player_index);
+    }
 
-        self.game.last_mini_game = max_votes_game;
+    pub(crate) fn get_next_mini_game(&mut self) {
+        self.game.last_mini_game = match self.leading_mini_game() {
+            Some((game, _)) => game,
+            None => rand::thread_rng().gen_range(0, 6),
+        };
 
         let mut mini_game_name = String::from("");
         let mut mini_game_description = String::from("");
@@ -150,8 +150,11 @@ pub(crate) struct HQMGame {
     pub(crate) period: u32,
     pub(crate) time: u32,
     pub(crate) time_break: u32,
+    pub(crate) break_reason: HQMBreakReason,
     pub(crate) is_intermission_goal: bool,
     pub(crate) paused: bool,
+    // Set by /pause, an admin logging in does not resume it
+    pub(crate) paused_by_admin: bool,
     pub(crate) game_id: u32,
     pub(crate) game_step: u32,
     pub(crate) game_over: bool,
@@ -160,6 +163,8 @@ pub(crate) struct HQMGame {
     pub(crate) logged_players_for_next: Vec<RHQMPlayer>,
     pub(crate) ranked_started: bool,
     pub(crate) ranked_count: usize,
+    // Ticks since the last login while waiting for the ranked queue to fill
+    pub(crate) ranked_stall_ticks: u32,
     pub(crate) game_players: Vec<RHQMGamePlayer>,
     pub(crate) shootout_red: usize,
     pub(crate) shootout_blue: usize,
@@ -168,8 +173,18 @@ pub(crate) struct HQMGame {
     pub(crate) shootout_red_score: Vec<String>,
     pub(crate) shootout_blue_score: Vec<String>,
     pub(crate) shootout_number: usize,
+    pub(crate) shootout_red_order: Vec<usize>,
+    pub(crate) shootout_blue_order: Vec<usize>,
     pub(crate) data_saved: bool,
     pub(crate) active: bool,
+    // Player name, team and game time of each touch before the last goal, most recent first
+    pub(crate) last_goal_touches: Vec<(String, HQMTeam, u32)>,
+    // Red and blue center of the last faceoff, until someone touches the puck
+    pub(crate) faceoff_centers: Option<(Option<String>, Option<String>)>,
+    // Faceoff wins and losses by player name
+    pub(crate) faceoff_records: HashMap<String, (u32, u32)>,
+    // Ticks of play the current offside warning has lasted
+    pub(crate) offside_warning_ticks: u32,
 
     pub(crate) mini_game_time: usize,
     pub(crate) mini_game_warmup: usize,
@@ -190,13 +205,21 @@ pub(crate) struct HQMGame {
     pub(crate) catcher_vectors: Vec<f32>,
 
     pub(crate) last_random_index: usize,
+    // Participant already picked by /nextplayer for the next mini-game attempt
+    pub(crate) picked_mini_game_player: Option<usize>,
 
     pub(crate) last_mini_game: usize,
     pub(crate) last_mini_game_changed: bool,
     pub(crate) force_intermission: bool,
 
+    // Set once the warmup clock has been extended to let the current mini-game attempt
+    // finish; the next completed attempt ends the warmup
     pub(crate) wait_for_end: bool,
 
+    // Ticks each puck of the current mini-game attempt has been at rest since it was last
+    // moving, by object index; pucks that never moved are not in the map
+    pub(crate) mini_game_stall_ticks: HashMap<usize, u32>,
+
     pub(crate) lastx: f32,
     pub(crate) lasty: f32,
     pub(crate) lastz: f32,
@@ -238,7 +261,8 @@ impl HQMGame {
             next_faceoff_spot: mid_faceoff,
             world: HQMGameWorld {
                 objects: object_vec,
-                puck_slots: config.warmup_pucks,
+                // Faceoffs always need a slot for the game puck
+                puck_slots: config.warmup_pucks.max(1),
                 rink,
                 gravity: 0.000680555,
                 limit_jump_speed: config.limit_jump_speed,
@@ -249,7 +273,9 @@ impl HQMGame {
             time: 30000,
             is_intermission_goal: false,
             time_break: 1000,
+            break_reason: HQMBreakReason::GameStart,
             paused: true,
+            paused_by_admin: false,
 
             game_over: false,
             game_id,
@@ -259,7 +285,8 @@ impl HQMGame {
             logged_players: Vec::new(),
             logged_players_for_next: Vec::new(),
             ranked_started: false,
-            ranked_count: 100,
+            ranked_count: config.ranked_count,
+            ranked_stall_ticks: 0,
             game_players: Vec::new(),
             shootout_red: 0,
             shootout_blue: 0,
@@ -282,7 +309,13 @@ impl HQMGame {
                 String::from("-"),
             ],
             shootout_number: 0,
+            shootout_red_order: vec![],
+            shootout_blue_order: vec![],
             data_saved: false,
+            last_goal_touches: vec![],
+            faceoff_centers: None,
+            faceoff_records: HashMap::new(),
+            offside_warning_ticks: 0,
             mini_game_time: 0,
             mini_game_warmup: 0,
             pucks_in_net: vec![],
@@ -297,10 +330,12 @@ impl HQMGame {
             catcher_vectors: vec![7.0, 10.0, 11.0, 10.0, 8.0],
             gk_puck_in_net: false,
             last_random_index: 0,
+            picked_mini_game_player: None,
             last_mini_game: rand::thread_rng().gen_range(0, 6),
             last_mini_game_changed: false,
             force_intermission: false,
             wait_for_end: false,
+            mini_game_stall_ticks: HashMap::new(),
             last_puck_point: 0.0,
             lastx: 0.0,
             lasty: 0.0,
@@ -1780,6 +1815,10 @@ pub(crate) struct RHQMGamePlayer {
     pub goals: usize,
     pub assists: usize,
     pub leaved_seconds: usize,
+    // Timed out while timeouts are exempt from the leave penalty, the rejoin clock is stopped
+    pub leave_exempt: bool,
+    // Did not come back before the rejoin clock ran out, loses leave_penalty points when the game is saved
+    pub left_game: bool,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
@@ -1806,6 +1845,35 @@ impl Display for HQMTeam {
     }
 }
 
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum HQMBreakReason {
+    GameStart,
+    Goal,
+    Offside,
+    Icing,
+    PeriodEnd,
+    Shootout,
+    GameOver,
+    Faceoff,
+    MiniGameVote,
+}
+
+impl Display for HQMBreakReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            HQMBreakReason::GameStart => write!(f, "Game start"),
+            HQMBreakReason::Goal => write!(f, "Goal"),
+            HQMBreakReason::Offside => write!(f, "Offside"),
+            HQMBreakReason::Icing => write!(f, "Icing"),
+            HQMBreakReason::PeriodEnd => write!(f, "End of period"),
+            HQMBreakReason::Shootout => write!(f, "Shootout"),
+            HQMBreakReason::GameOver => write!(f, "Game over"),
+            HQMBreakReason::Faceoff => write!(f, "Faceoff"),
+            HQMBreakReason::MiniGameVote => write!(f, "Mini-game vote"),
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum HQMGameState {
     Warmup,
@@ -5,37 +5,98 @@ use std::cmp::min;
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use crate::hqm_game::{
-    HQMGame, HQMGameObject, HQMGameState, HQMGameWorld, HQMIcingStatus, HQMMessage,
+    HQMBreakReason, HQMGame, HQMGameObject, HQMGameState, HQMGameWorld, HQMIcingStatus, HQMMessage,
     HQMOffsideStatus, HQMPlayerInput, HQMPuck, HQMRink, HQMRulesState, HQMSkaterHand, HQMTeam,
-    RHQMGamePlayer, RHQMPlayer,
+    RHQMPlayer,
 };
 use crate::hqm_parse::{HQMMessageReader, HQMMessageWriter, HQMObjectPacket};
 use crate::hqm_simulate::HQMSimulationEvent;
 use bytes::{Bytes, BytesMut};
+use chrono::Utc;
+use rand::seq::SliceRandom;
 use rand::Rng;
 use std::collections::VecDeque;
 use std::collections::{HashMap, HashSet};
 use std::f32::consts::{FRAC_PI_2, PI};
 use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio::net::UdpSocket;
-use tracing::info;
+use tokio::sync::mpsc;
+use tracing::{info, warn};
 
 use std::error::Error;
 use std::net::IpAddr;
+use std::panic::{self, AssertUnwindSafe};
 use std::path::PathBuf;
 use tokio::fs::File;
 use tokio::io::AsyncWriteExt;
 
 const GAME_HEADER: &[u8] = b"Hock";
 
+// The 2-bit object type selects which kind of object the client creates, it does not carry a color.
+// Type 1 is the only puck the client knows, so mini-game pucks can't be told apart this way.
+const OBJECT_TYPE_SKATER: u32 = 0;
+const OBJECT_TYPE_PUCK: u32 = 1;
+
+pub(crate) const DEFAULT_PLAYER_NAME: &str = "Noname";
+
 pub struct HQMSavedTick {
     packets: Vec<HQMObjectPacket>,
     time: Instant,
 }
 
-enum HQMServerReceivedData {
-    GameClientPacket { addr: SocketAddr, data: Bytes },
+const TICK_TIMING_SAMPLES: usize = 100;
+const TICK_PHASE_NAMES: [&str; 4] = ["input", "simulation", "events", "network"];
+
+// Recent durations of update_players_and_input, simulate_step, handle_events and send_updates
+pub(crate) struct HQMTickTimings {
+    samples: VecDeque<[Duration; 4]>,
+}
+
+impl HQMTickTimings {
+    fn new() -> Self {
+        HQMTickTimings {
+            samples: VecDeque::with_capacity(TICK_TIMING_SAMPLES),
+        }
+    }
+
+    fn push(&mut self, sample: [Duration; 4]) {
+        self.samples.truncate(TICK_TIMING_SAMPLES - 1);
+        self.samples.push_front(sample);
+    }
+
+    pub(crate) fn averages(&self) -> Option<[f32; 4]> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        let mut sum = [0f32; 4];
+        for sample in self.samples.iter() {
+            for (i, phase) in sample.iter().enumerate() {
+                sum[i] += phase.as_secs_f32() * 1000.0;
+            }
+        }
+        let count = self.samples.len() as f32;
+        Some([
+            sum[0] / count,
+            sum[1] / count,
+            sum[2] / count,
+            sum[3] / count,
+        ])
+    }
+}
+
+pub(crate) enum HQMServerReceivedData {
+    GameClientPacket {
+        addr: SocketAddr,
+        data: Bytes,
+    },
+    // Replies produced by background tasks (e.g. database lookups)
+    DirectedServerChat {
+        player_index: usize,
+        player_name: String,
+        messages: Vec<String>,
+    },
 }
 
 pub(crate) struct HQMServer {
@@ -48,6 +109,21 @@ pub(crate) struct HQMServer {
     game_alloc: u32,
     pub(crate) is_muted: bool,
     pub(crate) last_sec: u64,
+    pub(crate) admin_paused: bool,
+    pub(crate) tick_timings: HQMTickTimings,
+    pub(crate) msg_sender: Option<mpsc::Sender<HQMServerReceivedData>>,
+    audit_sender: Option<mpsc::UnboundedSender<String>>,
+    // Command and argument being processed, for the audit log
+    audit_command: Option<(String, String)>,
+    // Interval in ticks, message, ticks left until the next broadcast
+    pub(crate) scheduled_messages: Vec<(u32, String, u32)>,
+    pub(crate) local_addr: Option<SocketAddr>,
+    // Client version -> number of rejected join requests
+    pub(crate) rejected_versions: HashMap<u32, u32>,
+    // Whether the announce task notifies the master server
+    pub(crate) public: Arc<AtomicBool>,
+    // team_max, red_max and blue_max to go back to after the next faceoff, set by /onceteamsize
+    pub(crate) team_max_restore: Option<(usize, Option<usize>, Option<usize>)>,
 }
 
 impl HQMServer {
@@ -96,7 +172,11 @@ impl HQMServer {
         let player_count = self.player_count();
         writer.write_bits(8, player_count as u32);
         writer.write_bits(4, 4);
-        writer.write_bits(4, self.config.team_max as u32);
+        let team_max = self
+            .config
+            .team_max_for(HQMTeam::Red)
+            .max(self.config.team_max_for(HQMTeam::Blue));
+        writer.write_bits(4, team_max as u32);
 
         writer.write_bytes_aligned_padded(32, self.config.server_name.as_ref());
 
@@ -179,7 +259,7 @@ impl HQMServer {
                 let t1 = Instant::now();
                 if let Some(t2) = self.game.saved_ticks.get(diff).map(|x| x.time) {
                     if let Some(duration) = t1.checked_duration_since(t2) {
-                        player.last_ping.truncate(100 - 1);
+                        player.last_ping.truncate(self.config.ping_sample_count - 1);
                         player.last_ping.push_front(duration.as_secs_f32());
                     }
                 }
@@ -187,7 +267,13 @@ impl HQMServer {
         }
 
         player.inactivity = 0;
-        player.known_packet = packet;
+        player.known_packet = match player.resync_packet {
+            Some(resync_packet) if packet < resync_packet => u32::MAX,
+            _ => {
+                player.resync_packet = None;
+                packet
+            }
+        };
         player.input = input;
         player.game_id = current_game_id;
         player.known_msgpos = parser.read_u16_aligned() as usize;
@@ -211,7 +297,16 @@ impl HQMServer {
             return; // Ignore join request
         }
         let player_version = parser.read_bits(8);
-        if player_version != 55 {
+        if !self.config.accepted_versions.contains(&player_version) {
+            // Clients keep resending the join request, so only the first attempt of each version is logged
+            let count = self.rejected_versions.entry(player_version).or_insert(0);
+            if *count == 0 {
+                info!(
+                    "Rejected join from {:?} with client version {}",
+                    addr, player_version
+                );
+            }
+            *count += 1;
             return; // Not the right version
         }
         let current_slot = self.find_player_slot(addr);
@@ -241,6 +336,36 @@ impl HQMServer {
         };
     }
 
+    // Appends the current command to logs/admin-audit.log, called once the admin check passed
+    pub(crate) fn audit_admin_command(&self, player_index: usize, target_index: Option<usize>) {
+        if let (Some((command, arg)), Some(sender), Some(player)) = (
+            &self.audit_command,
+            &self.audit_sender,
+            &self.players[player_index],
+        ) {
+            let target = match target_index {
+                Some(target_index) => {
+                    match self.players.get(target_index).and_then(|x| x.as_ref()) {
+                        Some(target) => format!(" -> {} ({})", target.player_name, target_index),
+                        None => format!(" -> empty slot ({})", target_index),
+                    }
+                }
+                None => String::new(),
+            };
+            let line = format!(
+                "{} {} ({}) {}: /{} {}{}\n",
+                Utc::now().format("%Y-%m-%dT%H:%M:%S"),
+                player.player_name,
+                player_index,
+                player.addr.ip(),
+                command,
+                arg,
+                target
+            );
+            let _ = sender.send(line);
+        }
+    }
+
     fn set_hand(&mut self, hand: HQMSkaterHand, player_index: usize) {
         if let Some(player) = &mut self.players[player_index] {
             player.hand = hand;
@@ -262,6 +387,12 @@ impl HQMServer {
     }
 
     fn process_command(&mut self, command: &str, arg: &str, player_index: usize) {
+        self.audit_command = match command {
+            // These carry passwords
+            "admin" | "login" | "l" => None,
+            _ if self.audit_sender.is_some() => Some((command.to_string(), arg.to_string())),
+            _ => None,
+        };
         match command {
             "login" => {
                 self.login(player_index, arg);
@@ -328,6 +459,133 @@ impl HQMServer {
             "kickall" => {
                 self.kick_all_matching(player_index, arg, false);
             }
+            "kicknoname" => {
+                self.kick_noname(player_index);
+            }
+            "capacity" => {
+                self.capacity(player_index);
+            }
+            "perfdetail" => {
+                self.perf_detail(player_index);
+            }
+            "autoview" => {
+                if let Ok(auto_view_player_index) = arg.parse::<usize>() {
+                    if auto_view_player_index < self.players.len() {
+                        self.set_auto_view(player_index, auto_view_player_index);
+                    }
+                }
+            }
+            "activity" => {
+                if let Ok(activity_player_index) = arg.parse::<usize>() {
+                    if activity_player_index < self.players.len() {
+                        self.activity(player_index, activity_player_index);
+                    }
+                }
+            }
+            "resync" => {
+                if let Ok(resync_player_index) = arg.parse::<usize>() {
+                    if resync_player_index < self.players.len() {
+                        self.resync(player_index, resync_player_index);
+                    }
+                }
+            }
+            "wake" => {
+                if let Ok(wake_player_index) = arg.parse::<usize>() {
+                    if wake_player_index < self.players.len() {
+                        self.wake(player_index, wake_player_index);
+                    }
+                }
+            }
+            "lastgoaltouches" => {
+                self.last_goal_touches(player_index);
+            }
+            "nextplayer" => {
+                self.next_mini_game_player(player_index);
+            }
+            "nextminigame" => {
+                self.next_mini_game(player_index);
+            }
+            "votestatus" => {
+                self.vote_status(player_index);
+            }
+            "break" => {
+                self.break_info(player_index);
+            }
+            "resetminigame" => {
+                self.reset_mini_game(player_index);
+            }
+            "onceteamsize" => {
+                self.set_once_team_size(player_index, arg);
+            }
+            "startranked" => {
+                self.force_start_ranked(player_index);
+            }
+            "exportteams" => {
+                self.export_teams(player_index);
+            }
+            "teampoints" => {
+                self.team_points(player_index);
+            }
+            "stats" => {
+                self.stats(player_index);
+            }
+            "faceoffs" => {
+                self.faceoffs(player_index);
+            }
+            "setpoints" => {
+                self.set_points(player_index, arg);
+            }
+            "schedule" => {
+                self.schedule(player_index, arg);
+            }
+            "unschedule" => {
+                self.unschedule(player_index);
+            }
+            "versions" => {
+                self.versions(player_index);
+            }
+            "public" => {
+                self.set_public(player_index, arg);
+            }
+            "ranked" => {
+                self.set_ranked_login(player_index, arg);
+            }
+            "configall" => {
+                self.config_all(player_index);
+            }
+            "replaydir" => {
+                self.replay_dir(player_index);
+            }
+            "listen" => {
+                self.listen(player_index);
+            }
+            "pucks" => {
+                self.pucks(player_index);
+            }
+            "lines" => {
+                self.lines(player_index);
+            }
+            "mybest" => {
+                self.my_best(player_index, arg);
+            }
+            "current" => {
+                self.current(player_index);
+            }
+            "angle" => {
+                self.angle(player_index);
+            }
+            "gravity" => {
+                self.gravity(player_index);
+            }
+            "objects" => {
+                self.objects(player_index);
+            }
+            "replaystatus" => {
+                self.replay_status(player_index);
+            }
+            "eligible" => {
+                self.eligible(player_index);
+            }
             "ban" => {
                 if let Ok(kick_player_index) = arg.parse::<usize>() {
                     if kick_player_index < self.players.len() {
@@ -341,6 +599,9 @@ impl HQMServer {
             "clearbans" => {
                 self.clear_bans(player_index);
             }
+            "reloadbans" => {
+                self.reload_bans(player_index);
+            }
             "set" => {
                 let args = arg.split(" ").collect::<Vec<&str>>();
                 if args.len() > 1 {
@@ -436,11 +697,21 @@ impl HQMServer {
                                 self.set_offside_rule(player_index, arg);
                             }
                         }
+                        "warmuppucks" => {
+                            if let Some(arg) = args.get(1) {
+                                self.set_warmup_pucks(player_index, arg);
+                            }
+                        }
                         "teamsize" => {
                             if let Some(arg) = args.get(1) {
                                 self.set_team_size(player_index, arg);
                             }
                         }
+                        "goalbreak" => {
+                            if let Some(arg) = args.get(1) {
+                                self.set_goal_break(player_index, arg);
+                            }
+                        }
                         "teamparity" => {
                             if let Some(arg) = args.get(1) {
                                 self.set_team_parity(player_index, arg);
@@ -613,6 +884,7 @@ impl HQMServer {
             */
             _ => {} // matches have to be exhaustive
         }
+        self.audit_command = None;
     }
 
     fn list_players(&mut self, player_index: usize, first_index: usize) {
@@ -648,6 +920,19 @@ impl HQMServer {
     }
 
     fn view(&mut self, view_player_index: usize, player_index: usize) {
+        if self.config.restrict_view && view_player_index != player_index {
+            let is_admin = match &self.players[player_index] {
+                Some(player) => player.is_admin,
+                None => false,
+            };
+            if !is_admin {
+                self.add_directed_server_chat_message(
+                    "Viewing other players is restricted to admins".to_string(),
+                    player_index,
+                );
+                return;
+            }
+        }
         if view_player_index < self.players.len() {
             if let Some(view_player) = &self.players[view_player_index] {
                 let view_player_name = view_player.player_name.clone();
@@ -693,6 +978,193 @@ impl HQMServer {
         }
     }
 
+    // Spectators flagged with /autoview follow the last player to touch a puck
+    fn update_auto_view(&mut self) {
+        let interval = self.config.auto_view_interval * 100;
+        if interval == 0 || self.game.game_step % interval != 0 {
+            return;
+        }
+        let mut target = None;
+        for object in self.game.world.objects.iter() {
+            if let HQMGameObject::Puck(puck) = object {
+                if let Some(touch) = puck.touches.front() {
+                    target = Some(touch.player_index);
+                    break;
+                }
+            }
+        }
+        if let Some(target) = target {
+            if self.players[target].is_some() {
+                for player in self.players.iter_mut() {
+                    if let Some(player) = player {
+                        if player.auto_view && player.skater.is_none() {
+                            player.view_player_index = target;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn last_goal_touches(&mut self, player_index: usize) {
+        if self.game.last_goal_touches.is_empty() {
+            self.add_directed_server_chat_message(
+                "No goal touches recorded".to_string(),
+                player_index,
+            );
+            return;
+        }
+        // Oldest touch first, so the list reads like the play developed
+        let messages: Vec<String> = self
+            .game
+            .last_goal_touches
+            .iter()
+            .rev()
+            .map(|(name, team, time)| {
+                let seconds = time / 100;
+                format!("{} {} at {}:{:02}", team, name, seconds / 60, seconds % 60)
+            })
+            .collect();
+        for message in messages {
+            self.add_directed_server_chat_message(message, player_index);
+        }
+    }
+
+    fn replay_status(&mut self, player_index: usize) {
+        let msg = if self.config.replays_enabled {
+            format!(
+                "Replays enabled, {:.1} MB recorded this game",
+                self.game.replay_data.len() as f32 / (1024.0 * 1024.0)
+            )
+        } else {
+            "Replays disabled".to_string()
+        };
+        self.add_directed_server_chat_message(msg, player_index);
+    }
+
+    fn current(&mut self, player_index: usize) {
+        let msg = if self.game.period == 0
+            && !self.game.ranked_started
+            && self.game.time > 0
+            && self.game.next_game_player_index != 999
+            && !self.game.next_game_player.is_empty()
+        {
+            let game = Self::mini_game_name(self.game.last_mini_game);
+            if self.game.mini_game_warmup > 0 {
+                format!(
+                    "{}: {} starts in {} s",
+                    game,
+                    self.game.next_game_player,
+                    self.game.mini_game_warmup / 100
+                )
+            } else {
+                format!(
+                    "{}: {} is playing, {} s left",
+                    game,
+                    self.game.next_game_player,
+                    self.game.mini_game_time / 100
+                )
+            }
+        } else {
+            "No mini-game attempt in progress".to_string()
+        };
+        self.add_directed_server_chat_message(msg, player_index);
+    }
+
+    fn gravity(&mut self, player_index: usize) {
+        // Gravity is stored per tick squared, so the /cheat gravity units of 1/10000 are m/s^2
+        let msg = format!(
+            "Gravity {:.2} m/s^2 (normal {:.2})",
+            self.game.world.gravity * 10000.0,
+            0.000680555 * 10000.0
+        );
+        self.add_directed_server_chat_message(msg, player_index);
+    }
+
+    fn angle(&mut self, player_index: usize) {
+        let puck_pos = self
+            .game
+            .world
+            .objects
+            .iter()
+            .find_map(|object| match object {
+                HQMGameObject::Puck(puck) => Some(puck.body.pos),
+                _ => None,
+            });
+        let puck_pos = match puck_pos {
+            Some(pos) => pos,
+            None => {
+                self.add_directed_server_chat_message(String::from("No puck"), player_index);
+                return;
+            }
+        };
+
+        let rink = &self.game.world.rink;
+        let mut msgs = vec![];
+        for (name, net) in [
+            ("Red", &rink.red_lines_and_net.net),
+            ("Blue", &rink.blue_lines_and_net.net),
+        ]
+        .iter()
+        {
+            let center = net.left_post + (net.right_post - net.left_post) / 2.0;
+            let mut diff = puck_pos - center;
+            diff.y = 0.0;
+            let distance = diff.norm();
+            // 0 degrees is straight in front of the net, more than 90 is behind the goal line
+            let angle = if distance > 0.0 {
+                (diff.dot(&net.normal) / distance)
+                    .min(1.0)
+                    .max(-1.0)
+                    .acos()
+                    .to_degrees()
+            } else {
+                0.0
+            };
+            msgs.push(format!(
+                "{} net: {:.2} m, {:.0} degrees",
+                name, distance, angle
+            ));
+        }
+        for msg in msgs {
+            self.add_directed_server_chat_message(msg, player_index);
+        }
+    }
+
+    fn objects(&mut self, player_index: usize) {
+        let mut pucks = 0;
+        let mut skaters = 0;
+        let mut free = 0;
+        for object in self.game.world.objects.iter() {
+            match object {
+                HQMGameObject::Puck(_) => pucks += 1,
+                HQMGameObject::Player(_) => skaters += 1,
+                HQMGameObject::None => free += 1,
+            }
+        }
+        let msg = format!(
+            "Objects: {} pucks, {} skaters, {} free of {}",
+            pucks,
+            skaters,
+            free,
+            self.game.world.objects.len()
+        );
+        self.add_directed_server_chat_message(msg, player_index);
+    }
+
+    fn break_info(&mut self, player_index: usize) {
+        let msg = if self.game.time_break > 0 {
+            format!(
+                "{}, {}s left",
+                self.game.break_reason,
+                (self.game.time_break + 99) / 100
+            )
+        } else {
+            "No break in progress".to_string()
+        };
+        self.add_directed_server_chat_message(msg, player_index);
+    }
+
     fn ping(&mut self, ping_player_index: usize, player_index: usize) {
         if ping_player_index < self.players.len() {
             if let Some(ping_player) = &self.players[ping_player_index] {
@@ -700,11 +1172,12 @@ impl HQMServer {
                     let msg = format!("No ping values found for {}", ping_player.player_name);
                     self.add_directed_server_chat_message(msg, player_index);
                 } else {
-                    let n = ping_player.last_ping.len() as f32;
+                    let sample_count = self.config.ping_sample_count;
+                    let n = ping_player.last_ping.len().min(sample_count) as f32;
                     let mut min = f32::INFINITY;
                     let mut max = f32::NEG_INFINITY;
                     let mut sum = 0f32;
-                    for i in ping_player.last_ping.iter() {
+                    for i in ping_player.last_ping.iter().take(sample_count) {
                         min = min.min(*i);
                         max = max.max(*i);
                         sum += *i;
@@ -712,7 +1185,7 @@ impl HQMServer {
                     let avg = sum / n;
                     let dev = {
                         let mut s = 0f32;
-                        for i in ping_player.last_ping.iter() {
+                        for i in ping_player.last_ping.iter().take(sample_count) {
                             s += (*i - avg).powi(2);
                         }
                         (s / n).sqrt()
@@ -823,47 +1296,35 @@ impl HQMServer {
                 let msg = format!("{} exited", player_name);
                 self.add_server_chat_message(msg);
 
-                if self.game.ranked_started {
-                    let mut exist = false;
-                    let mut leaved_seconds = 0;
-                    let mut index = 0;
-                    let mut found_index = 0;
-                    for i in self.game.game_players.iter() {
-                        match i {
-                            RHQMGamePlayer {
-                                player_i_r: _,
-                                player_name_r,
-                                player_points: _,
-                                player_team: _,
-                                goals: _,
-                                assists: _,
-                                leaved_seconds,
-                            } => {
-                                if player_name_r == &player_name {
-                                    exist = true;
-                                    found_index = index;
-                                }
-                            }
-                        }
-                        index += 1;
-                    }
-
-                    if exist {
-                        leaved_seconds = self.game.game_players[found_index].leaved_seconds;
-                    }
-
-                    if exist {
-                        let secs = leaved_seconds % 60;
-                        let minutes = (leaved_seconds - secs) / 60;
-                        let msg = format!("{} have {}m {}s to rejoin", player_name, minutes, secs);
-                        self.add_server_chat_message(msg);
-                    }
-                }
+                self.ranked_player_left(&player_name, false);
             }
             None => {}
         }
     }
 
+    // Starts the rejoin window of a ranked player who exited or timed out
+    fn ranked_player_left(&mut self, player_name: &str, timed_out: bool) {
+        if !self.game.ranked_started {
+            return;
+        }
+        let exempt = timed_out && !self.config.timeout_leave_penalty;
+        if let Some(game_player) = self
+            .game
+            .game_players
+            .iter_mut()
+            .find(|p| p.player_name_r == player_name)
+        {
+            game_player.leave_exempt = exempt;
+            let leaved_seconds = game_player.leaved_seconds;
+            if !exempt {
+                let secs = leaved_seconds % 60;
+                let minutes = (leaved_seconds - secs) / 60;
+                let msg = format!("{} have {}m {}s to rejoin", player_name, minutes, secs);
+                self.add_server_chat_message(msg);
+            }
+        }
+    }
+
     #[allow(dead_code)]
     pub(crate) fn set_team(
         &mut self,
@@ -988,6 +1449,10 @@ impl HQMServer {
 
                 self.players[player_index] = Some(new_player);
 
+                if self.config.pause_without_admin && !self.has_admin() {
+                    self.pause_without_admin();
+                }
+
                 Some(player_index)
             }
             _ => None,
@@ -1036,19 +1501,29 @@ impl HQMServer {
         }
 
         if admin_check {
-            let mut admin_found = false;
+            if !self.has_admin() {
+                self.allow_join = true;
 
-            for p in self.players.iter_mut() {
-                if let Some(player) = p {
-                    if player.is_admin {
-                        admin_found = true;
-                    }
+                if self.config.pause_without_admin {
+                    self.pause_without_admin();
                 }
             }
+        }
+    }
 
-            if !admin_found {
-                self.allow_join = true;
-            }
+    pub(crate) fn has_admin(&self) -> bool {
+        self.players.iter().any(|p| match p {
+            Some(player) => player.is_admin,
+            None => false,
+        })
+    }
+
+    fn pause_without_admin(&mut self) {
+        if self.config.mode == HQMServerMode::Match && !self.admin_paused {
+            self.admin_paused = true;
+            self.game.paused = true;
+            info!("Game paused, no admin present");
+            self.add_server_chat_message(String::from("Game paused until an admin is present"));
         }
     }
 
@@ -1100,15 +1575,63 @@ impl HQMServer {
         self.add_directed_user_chat_message2(message, receiver_index, None);
     }
 
+    // Mini-game progress and results; broadcast says whether the message is public by default
+    fn add_mini_game_result_message(&mut self, message: String, broadcast: bool) {
+        let broadcast = match self.config.mini_game_results {
+            HQMMiniGameResultsConfiguration::Default => broadcast,
+            HQMMiniGameResultsConfiguration::Broadcast => true,
+            HQMMiniGameResultsConfiguration::Private => false,
+        };
+        if broadcast {
+            self.add_server_chat_message(message);
+        } else if self.game.next_game_player_index != 999 {
+            self.add_directed_server_chat_message(message, self.game.next_game_player_index);
+        }
+    }
+
+    // The slot may have been taken over by someone else while the reply was being prepared
+    fn add_delayed_directed_server_chat_messages(
+        &mut self,
+        receiver_index: usize,
+        receiver_name: &str,
+        messages: Vec<String>,
+    ) {
+        let still_connected = match &self.players[receiver_index] {
+            Some(player) => player.player_name == receiver_name,
+            None => false,
+        };
+        if still_connected {
+            for message in messages {
+                self.add_directed_server_chat_message(message, receiver_index);
+            }
+        }
+    }
+
     pub(crate) fn add_global_message(&mut self, message: HQMMessage, persistent: bool) {
         let rc = Rc::new(message);
         self.game.replay_messages.push(rc.clone());
         if persistent {
             self.game.persistent_messages.push(rc.clone());
         }
-        for player in self.players.iter_mut() {
+        // Player chat can be hidden from spectators during a match, server messages always go through
+        let hide_from_spectators = match &*rc {
+            HQMMessage::Chat {
+                player_index: Some(sender_index),
+                ..
+            } if !self.config.spectators_see_chat && self.game.period != 0 => Some(*sender_index),
+            _ => None,
+        };
+        for (player_index, player) in self.players.iter_mut().enumerate() {
             match player {
                 Some(player) => {
+                    if let Some(sender_index) = hide_from_spectators {
+                        if player.skater.is_none()
+                            && !player.is_admin
+                            && player_index != sender_index
+                        {
+                            continue;
+                        }
+                    }
                     player.messages.push(rc.clone());
                 }
                 _ => (),
@@ -1170,28 +1693,14 @@ impl HQMServer {
         }
 
         let mut messages = vec![];
-        let mut chat_messages = vec![];
+        let mut timed_out = vec![];
         let players = &mut self.players;
         let world = &mut self.game.world;
         for (player_index, player_option) in players.iter_mut().enumerate() {
             if let Some(player) = player_option {
                 player.inactivity += 1;
                 if player.inactivity > 500 {
-                    if let Some(object_index) = player.skater {
-                        world.objects[object_index] = HQMGameObject::None;
-                    }
-                    info!("{} ({}) timed out", player.player_name, player_index);
-                    messages.push(HQMMessage::PlayerUpdate {
-                        player_name: player.player_name.clone(),
-                        object: None,
-                        player_index,
-                        in_server: false,
-                    });
-                    let chat_msg = format!("{} timed out", player.player_name);
-                    chat_messages.push(chat_msg);
-
-                    *player_option = None;
-
+                    timed_out.push((player_index, player.player_name.clone()));
                     continue;
                 }
 
@@ -1251,24 +1760,119 @@ impl HQMServer {
         for message in messages {
             self.add_global_message(message, true);
         }
-        for message in chat_messages {
-            self.add_server_chat_message(message);
+        // Timeouts are handled like exits
+        for (player_index, player_name) in timed_out {
+            self.remove_player(player_index);
+            info!("{} ({}) timed out", player_name, player_index);
+            let msg = format!("{} timed out", player_name);
+            self.add_server_chat_message(msg);
+
+            self.ranked_player_left(&player_name, true);
+        }
+    }
+
+    fn update_scheduled_messages(&mut self) {
+        let mut due = vec![];
+        for (interval, text, countdown) in self.scheduled_messages.iter_mut() {
+            *countdown = countdown.saturating_sub(1);
+            if *countdown == 0 {
+                *countdown = *interval;
+                due.push(text.clone());
+            }
+        }
+        for text in due {
+            for line in split_chat_message(&text) {
+                self.add_server_chat_message(line);
+            }
+        }
+    }
+
+    // Warns once when nobody has logged in for a while and the ranked queue is not full
+    fn check_ranked_stall(&mut self) {
+        self.game.ranked_stall_ticks += 1;
+        let warning_ticks = self.config.ranked_stall_warning * 100;
+        if warning_ticks != 0 && self.game.ranked_stall_ticks == warning_ticks {
+            let logged = self.game.logged_players.len();
+            info!(
+                "Ranked queue stalled at {}/{} logged in players",
+                logged, self.game.ranked_count
+            );
+            self.add_server_chat_message(format!(
+                "Ranked queue stalled at {}/{}, admins can /startranked",
+                logged, self.game.ranked_count
+            ));
+        }
+    }
+
+    // Maps a team's shootout counter to the position of a player within that team.
+    // A new order is drawn at the start of every round of shooters, so nobody shoots
+    // twice before everyone on the team has shot once.
+    fn shootout_position(&mut self, team: usize, counter: usize, shooting: bool) -> usize {
+        let team_size = self
+            .game
+            .game_players
+            .iter()
+            .filter(|p| p.player_team == team)
+            .count();
+        if team_size == 0 {
+            return 0;
+        }
+        let order = if team == 0 {
+            &mut self.game.shootout_red_order
+        } else {
+            &mut self.game.shootout_blue_order
+        };
+        if order.len() != team_size || (shooting && counter % team_size == 0) {
+            *order = (0..team_size).collect();
+            if self.config.shootout_order == HQMShootoutOrder::Random {
+                order.shuffle(&mut rand::thread_rng());
+            }
         }
+        order[counter % team_size]
     }
 
     async fn tick(&mut self, socket: &UdpSocket) {
         if self.player_count() != 0 {
             self.game.active = true;
-            let packets = tokio::task::block_in_place(|| {
+            let tick_start = Instant::now();
+            let mut timings = [Duration::default(); 4];
+            // Everything up to building the packets runs synchronously and can be recovered from a panic
+            let step = AssertUnwindSafe(|| {
+                let phase_start = Instant::now();
                 self.update_players_and_input();
+                timings[0] = phase_start.elapsed();
+
+                let phase_start = Instant::now();
                 let events = self.game.world.simulate_step();
+                timings[1] = phase_start.elapsed();
+                self.update_auto_view();
+                self.update_scheduled_messages();
                 if self.config.mode == HQMServerMode::Match {
+                    // Automatic unpausing (logins, mini-games) must not resume unsupervised play
+                    if self.admin_paused {
+                        self.game.paused = true;
+                    }
+                    let phase_start = Instant::now();
                     self.handle_events(events);
+                    timings[2] = phase_start.elapsed();
                     self.update_clock();
                     self.game.update_game_state();
 
-                    if self.game.period > 3 && self.game.red_score == self.game.blue_score {
-                        if self.game.time_break > 1700 {
+                    // Shootout: every attempt is followed by a break of time_intermission seconds.
+                    // During the first shootout_decision_time seconds of that break the game ends
+                    // if the shootout is decided, during the next shootout_setup_time seconds the
+                    // next shooter and goalie are placed, and when the break is over the attempt
+                    // starts with shootout_attempt_time seconds on the clock.
+                    let break_length = self.config.time_intermission * 100;
+                    let decision_end =
+                        break_length.saturating_sub(self.config.shootout_decision_time * 100);
+                    let setup_end =
+                        decision_end.saturating_sub(self.config.shootout_setup_time * 100);
+                    if self.game.period > self.regulation_periods()
+                        && self.game.red_score == self.game.blue_score
+                        && !self.config.allow_ties
+                    {
+                        if self.game.time_break > decision_end {
                             self.game.shootout_randomized = false;
 
                             if self.game.shoutout_red_start {
@@ -1294,9 +1898,8 @@ impl HQMServer {
                                 }
                             }
                         }
-                        if self.game.time_break > 1500 && self.game.time_break < 1700 {
+                        if self.game.time_break > setup_end && self.game.time_break < decision_end {
                             if self.game.shootout_randomized == false {
-                                self.config.team_max = 1;
                                 self.force_players_off_ice_by_system();
 
                                 let mut red_stat = String::from("").to_owned();
@@ -1355,9 +1958,10 @@ impl HQMServer {
                                     let mut found_index_red = 0;
                                     let mut found_index_blue = 0;
 
-                                    let red_index = self.game.shootout_red % self.game.ranked_count;
+                                    let red_index =
+                                        self.shootout_position(0, self.game.shootout_red, true);
                                     let blue_index =
-                                        self.game.shootout_blue % self.game.ranked_count;
+                                        self.shootout_position(1, self.game.shootout_blue, false);
 
                                     let mut red_att = 0;
                                     let mut blue_gk = 1;
@@ -1406,9 +2010,10 @@ impl HQMServer {
                                     let mut found_index_red = 0;
                                     let mut found_index_blue = 0;
 
-                                    let red_index = self.game.shootout_red % self.game.ranked_count;
+                                    let red_index =
+                                        self.shootout_position(0, self.game.shootout_red, false);
                                     let blue_index =
-                                        self.game.shootout_blue % self.game.ranked_count;
+                                        self.shootout_position(1, self.game.shootout_blue, true);
 
                                     let mut red_att = 0;
                                     let mut blue_gk = 1;
@@ -1466,6 +2071,30 @@ impl HQMServer {
 
                 get_packets(&self.game.world.objects)
             });
+            let packets = match tokio::task::block_in_place(|| panic::catch_unwind(step)) {
+                Ok(packets) => packets,
+                Err(e) => {
+                    if !self.config.recover_from_panic {
+                        panic::resume_unwind(e);
+                    }
+                    let reason = if let Some(s) = e.downcast_ref::<&str>() {
+                        s.to_string()
+                    } else if let Some(s) = e.downcast_ref::<String>() {
+                        s.clone()
+                    } else {
+                        String::from("unknown")
+                    };
+                    warn!(
+                        "Tick panicked in game {} ({}), starting a new game",
+                        self.game.game_id, reason
+                    );
+                    self.new_game();
+                    self.add_server_chat_message(String::from(
+                        "Server error, a new game was started",
+                    ));
+                    return;
+                }
+            };
 
             let mut write_buf = vec![0u8; 4096];
             self.game
@@ -1479,10 +2108,33 @@ impl HQMServer {
             self.game.packet = self.game.packet.wrapping_add(1);
             self.game.game_step = self.game.game_step.wrapping_add(1);
 
+            let phase_start = Instant::now();
             send_updates(&self.game, &self.players, socket, &mut write_buf).await;
+            timings[3] = phase_start.elapsed();
+            self.tick_timings.push(timings);
+
             if self.config.replays_enabled {
                 write_replay(&mut self.game, &mut write_buf);
             }
+
+            let tick_duration = tick_start.elapsed();
+            if self.config.tick_budget_ms > 0
+                && tick_duration > Duration::from_millis(self.config.tick_budget_ms)
+            {
+                let mut slowest = 0;
+                for (i, phase) in timings.iter().enumerate() {
+                    if *phase > timings[slowest] {
+                        slowest = i;
+                    }
+                }
+                warn!(
+                    "Tick took {:.2} ms (budget {} ms), slowest phase {} took {:.2} ms",
+                    tick_duration.as_secs_f32() * 1000.0,
+                    self.config.tick_budget_ms,
+                    TICK_PHASE_NAMES[slowest],
+                    timings[slowest].as_secs_f32() * 1000.0
+                );
+            }
         } else if self.game.active {
             info!("Game {} abandoned", self.game.game_id);
             self.new_game();
@@ -1491,7 +2143,7 @@ impl HQMServer {
     }
 
     fn call_goal(&mut self, team: HQMTeam, puck: usize) {
-        if self.game.period <= 3 {
+        if self.game.period <= self.regulation_periods() {
             if team == HQMTeam::Red {
                 self.game.red_score += 1;
             } else if team == HQMTeam::Blue {
@@ -1500,10 +2152,12 @@ impl HQMServer {
         }
 
         self.game.time_break = self.config.time_break * 100;
+        self.game.break_reason = HQMBreakReason::Goal;
         self.game.is_intermission_goal = true;
         self.game.next_faceoff_spot = self.game.world.rink.center_faceoff_spot.clone();
-        if self.game.period > 3 {
+        if self.game.period > self.regulation_periods() {
             self.game.time_break = self.config.time_intermission * 100;
+            self.game.break_reason = HQMBreakReason::Shootout;
             self.game.time = 0;
 
             if team == HQMTeam::Red {
@@ -1513,19 +2167,44 @@ impl HQMServer {
             }
         }
 
-        if self.game.red_score + self.config.mercy_rule == self.game.blue_score {
+        // A mercy rule of 0 disables it
+        let score_difference = if self.game.red_score > self.game.blue_score {
+            self.game.red_score - self.game.blue_score
+        } else {
+            self.game.blue_score - self.game.red_score
+        };
+        if self.config.mercy_rule > 0 && score_difference >= self.config.mercy_rule {
             self.game.time_break = self.config.time_intermission * 100;
+            self.game.break_reason = HQMBreakReason::GameOver;
             self.game.game_over = true;
         }
 
-        if self.game.red_score == self.game.blue_score + self.config.mercy_rule {
-            self.game.time_break = self.config.time_intermission * 100;
-            self.game.game_over = true;
+        // Run-and-gun: a one tick break makes update_clock drop the puck at center in this tick
+        if !self.config.goal_break
+            && self.game.period <= self.regulation_periods()
+            && !self.game.game_over
+        {
+            self.game.time_break = 1;
         }
 
         let mut goal_scorer_index = None;
         let mut assist_index = None;
 
+        if let HQMGameObject::Puck(this_puck) = &self.game.world.objects[puck] {
+            let players = &self.players;
+            self.game.last_goal_touches = this_puck
+                .touches
+                .iter()
+                .map(|touch| {
+                    let name = match &players[touch.player_index] {
+                        Some(player) => player.player_name.clone(),
+                        None => String::from("?"),
+                    };
+                    (name, touch.team, touch.time)
+                })
+                .collect();
+        }
+
         if let HQMGameObject::Puck(this_puck) = &mut self.game.world.objects[puck] {
             for touch in this_puck.touches.iter() {
                 if touch.team == team {
@@ -1541,6 +2220,8 @@ impl HQMServer {
                             .unwrap();
 
                         self.game.game_players[index].goals += 1;
+                    } else if !self.config.assists_enabled {
+                        break;
                     } else if assist_index.is_none() && Some(player_index) != goal_scorer_index {
                         assist_index = Some(player_index);
 
@@ -1573,6 +2254,7 @@ impl HQMServer {
             .rink
             .get_offside_faceoff_spot(pass_origin, team);
         self.game.time_break = self.config.time_break * 100;
+        self.game.break_reason = HQMBreakReason::Offside;
         self.game.offside_status = HQMOffsideStatus::Offside(team);
         self.add_server_chat_message(String::from("Offside"));
     }
@@ -1584,6 +2266,7 @@ impl HQMServer {
             .rink
             .get_icing_faceoff_spot(pass_origin, team);
         self.game.time_break = self.config.time_break * 100;
+        self.game.break_reason = HQMBreakReason::Icing;
         self.game.icing_status = HQMIcingStatus::Icing(team);
         self.add_server_chat_message(String::from("Icing"));
     }
@@ -1619,6 +2302,8 @@ impl HQMServer {
                         let touching_team = skater.team;
                         let faceoff_position = skater.faceoff_position.clone();
 
+                        self.record_faceoff_result(touching_team);
+
                         if let HQMGameObject::Puck(puck) = &mut self.game.world.objects[puck] {
                             puck.add_touch(
                                 this_connected_player_index,
@@ -1744,6 +2429,18 @@ impl HQMServer {
                 self.add_server_chat_message(String::from("Offside waved off"));
             }
         }
+
+        // A warning that is never resolved would keep play going in an offside situation
+        if let HQMOffsideStatus::Warning(team, p, _) = &self.game.offside_status {
+            self.game.offside_warning_ticks += 1;
+            let timeout = self.config.offside_warning_ticks;
+            if timeout > 0 && self.game.offside_warning_ticks >= timeout {
+                let (team, copy) = (*team, p.clone());
+                self.call_offside(team, &copy);
+            }
+        } else {
+            self.game.offside_warning_ticks = 0;
+        }
     }
 
     pub(crate) fn new_game(&mut self) {
@@ -1759,15 +2456,16 @@ impl HQMServer {
         if self.config.replays_enabled && old_game.period != 0 {
             let time = old_game.start_time.format("%Y-%m-%dT%H%M%S").to_string();
             let file_name = format!("{}.{}.hrp", self.config.server_name, time);
+            let replay_dir = self.config.replay_dir.clone();
             let replay_data = old_game.replay_data;
 
             let game_id = old_game.game_id;
 
             tokio::spawn(async move {
-                if tokio::fs::create_dir_all("replays").await.is_err() {
+                if tokio::fs::create_dir_all(&replay_dir).await.is_err() {
                     return;
                 };
-                let path: PathBuf = ["replays", &file_name].iter().collect();
+                let path: PathBuf = [&replay_dir, &file_name].iter().collect();
 
                 let mut file_handle = match File::create(path).await {
                     Ok(file) => file,
@@ -1791,8 +2489,8 @@ impl HQMServer {
         info!("New game {} started", self.game.game_id);
         self.game_alloc += 1;
 
-        let puck_line_start =
-            self.game.world.rink.width / 2.0 - 0.4 * ((self.config.warmup_pucks - 1) as f32);
+        let puck_line_start = self.game.world.rink.width / 2.0
+            - 0.4 * (self.config.warmup_pucks.saturating_sub(1) as f32);
 
         for i in 0..self.config.warmup_pucks {
             let pos = Point3::new(
@@ -1813,6 +2511,7 @@ impl HQMServer {
 
                 player.known_msgpos = 0;
                 player.known_packet = u32::MAX;
+                player.resync_packet = None;
                 player.messages.clear();
                 let update = HQMMessage::PlayerUpdate {
                     player_name: player.player_name.clone(),
@@ -1909,7 +2608,117 @@ impl HQMServer {
         res
     }
 
+    // Ranked players who rejoined during the game come back as spectators, possibly in a
+    // different slot, so put them back on the team they were assigned when the game started
+    fn restore_ranked_teams(&mut self) {
+        let mut restore = vec![];
+        for (game_player_index, game_player) in self.game.game_players.iter().enumerate() {
+            let connected = self.players.iter().enumerate().find(|(_, p)| match p {
+                Some(p) => p.player_name == game_player.player_name_r,
+                None => false,
+            });
+            if let Some((player_index, Some(player))) = connected {
+                if player.skater.is_none() {
+                    let team = if game_player.player_team == 0 {
+                        HQMTeam::Red
+                    } else {
+                        HQMTeam::Blue
+                    };
+                    restore.push((game_player_index, player_index, team));
+                }
+            }
+        }
+
+        for (game_player_index, player_index, team) in restore {
+            self.game.game_players[game_player_index].player_i_r = player_index;
+            self.set_team(player_index, Some(team));
+        }
+    }
+
+    // The team that touches the puck first after a faceoff wins it
+    fn record_faceoff_result(&mut self, team: HQMTeam) {
+        if let Some((red_center, blue_center)) = self.game.faceoff_centers.take() {
+            let (winner, loser) = match team {
+                HQMTeam::Red => (red_center, blue_center),
+                HQMTeam::Blue => (blue_center, red_center),
+            };
+            if let Some(winner) = winner {
+                self.game.faceoff_records.entry(winner).or_insert((0, 0)).0 += 1;
+            }
+            if let Some(loser) = loser {
+                self.game.faceoff_records.entry(loser).or_insert((0, 0)).1 += 1;
+            }
+        }
+    }
+
+    fn stats(&mut self, player_index: usize) {
+        if self.game.game_players.is_empty() {
+            self.add_directed_server_chat_message(
+                "No ranked game in progress".to_string(),
+                player_index,
+            );
+            return;
+        }
+        let show_ratio = self.config.stats_show_ratio;
+        let mut game_players: Vec<_> = self.game.game_players.iter().collect();
+        game_players.sort_by(|a, b| (b.goals + b.assists).cmp(&(a.goals + a.assists)));
+        let stats: Vec<String> = game_players
+            .into_iter()
+            .map(|p| {
+                let line = format!(
+                    "{} {}+{}={}",
+                    p.player_name_r,
+                    p.goals,
+                    p.assists,
+                    p.goals + p.assists
+                );
+                if !show_ratio {
+                    line
+                } else if p.goals == 0 {
+                    format!("{} (A/G -)", line)
+                } else {
+                    format!("{} (A/G {:.2})", line, p.assists as f32 / p.goals as f32)
+                }
+            })
+            .collect();
+        for msg in chat_lines(&stats) {
+            self.add_directed_server_chat_message(msg, player_index);
+        }
+    }
+
+    fn faceoffs(&mut self, player_index: usize) {
+        if self.game.faceoff_records.is_empty() {
+            self.add_directed_server_chat_message(
+                "No faceoffs taken yet".to_string(),
+                player_index,
+            );
+            return;
+        }
+        let mut records: Vec<_> = self.game.faceoff_records.iter().collect();
+        records.sort_by(|a, b| a.0.cmp(b.0));
+        let records: Vec<String> = records
+            .into_iter()
+            .map(|(name, (wins, losses))| {
+                format!(
+                    "{} {}-{} ({}%)",
+                    name,
+                    wins,
+                    losses,
+                    wins * 100 / (wins + losses)
+                )
+            })
+            .collect();
+        for msg in chat_lines(&records) {
+            self.add_directed_server_chat_message(msg, player_index);
+        }
+    }
+
     fn do_faceoff(&mut self) {
+        // The shootout picks its own skaters
+        if self.game.ranked_started && self.game.period <= self.regulation_periods() {
+            self.restore_ranked_teams();
+        }
+
         let faceoff_spot = &self.game.next_faceoff_spot;
 
         let positions = Self::get_faceoff_positions(
@@ -1920,6 +2729,25 @@ impl HQMServer {
 
         let puck_pos = &faceoff_spot.center_position + &(1.5f32 * Vector3::y());
 
+        // Shootout attempts also start with a faceoff, they don't count for /faceoffs
+        if self.game.period > 0 && self.game.period <= self.regulation_periods() {
+            let mut red_center = None;
+            let mut blue_center = None;
+            for (player_index, (team, faceoff_position)) in positions.iter() {
+                if faceoff_position == "C" {
+                    if let Some(player) = &self.players[*player_index] {
+                        match team {
+                            HQMTeam::Red => red_center = Some(player.player_name.clone()),
+                            HQMTeam::Blue => blue_center = Some(player.player_name.clone()),
+                        }
+                    }
+                }
+            }
+            self.game.faceoff_centers = Some((red_center, blue_center));
+        } else {
+            self.game.faceoff_centers = None;
+        }
+
         self.game.world.objects = vec![HQMGameObject::None; 32];
         self.game.world.create_puck_object(
             puck_pos.clone(),
@@ -2000,6 +2828,73 @@ impl HQMServer {
         for message in messages {
             self.add_global_message(message, true);
         }
+
+        if let Some((team_max, red_max, blue_max)) = self.team_max_restore.take() {
+            self.config.team_max = team_max;
+            self.config.red_max = red_max;
+            self.config.blue_max = blue_max;
+            info!("Team size restored to {}", team_max);
+        }
+    }
+
+    // Removes warmup and mini-game pucks when the match starts, the opening faceoff then
+    // creates the only puck of the match
+    fn clear_pucks(&mut self) {
+        for object in self.game.world.objects.iter_mut() {
+            if let HQMGameObject::Puck(_) = object {
+                *object = HQMGameObject::None;
+            }
+        }
+    }
+
+    // Periods before the shootout
+    pub(crate) fn regulation_periods(&self) -> u32 {
+        if self.game.ranked_started {
+            self.config.ranked_periods
+        } else {
+            3
+        }
+    }
+
+    fn remove_stalled_mini_game_pucks(&mut self) {
+        // Air puck already ends the attempt when the puck stops
+        if self.config.mini_game_stall_time == 0 || self.game.last_mini_game == 3 {
+            return;
+        }
+        let limit = self.config.mini_game_stall_time * 100;
+
+        let mut stalled = vec![];
+        let mut remaining = 0;
+        for object in self.game.world.objects.iter() {
+            if let HQMGameObject::Puck(puck) = object {
+                // Velocity is stored per tick, this is 0.05 m/s
+                if puck.body.linear_velocity.norm() > 0.0005 {
+                    self.game.mini_game_stall_ticks.insert(puck.index, 0);
+                } else if let Some(ticks) = self.game.mini_game_stall_ticks.get_mut(&puck.index) {
+                    *ticks += 1;
+                    if *ticks >= limit {
+                        stalled.push(puck.index);
+                        continue;
+                    }
+                }
+                remaining += 1;
+            }
+        }
+
+        if stalled.is_empty() {
+            return;
+        }
+        for index in stalled {
+            self.game.world.objects[index] = HQMGameObject::None;
+            self.game.mini_game_stall_ticks.remove(&index);
+        }
+        self.add_server_chat_message(String::from("Stalled puck removed"));
+
+        // The other mini-games bring in a new puck or end the attempt at their next round,
+        // speed shots would wait for its clock with nothing left to shoot
+        if remaining == 0 && self.game.last_mini_game == 0 {
+            self.game.mini_game_time = 1;
+        }
     }
 
     fn update_clock(&mut self) {
@@ -2009,13 +2904,19 @@ impl HQMServer {
                 if self.game.time_break == 0 {
                     self.game.is_intermission_goal = false;
                     if self.game.game_over {
+                        if self.config.award_ranked_points
+                            && self.game.ranked_started
+                            && !self.game.data_saved
+                        {
+                            self.award_ranked_points();
+                        }
                         self.new_game();
                     } else {
                         if self.game.time == 0 {
                             self.game.time = self.config.time_period * 100;
 
-                            if self.game.period > 3 {
-                                self.game.time = 1500;
+                            if self.game.period > self.regulation_periods() {
+                                self.game.time = self.config.shootout_attempt_time * 100;
                             }
                         }
                         self.do_faceoff();
@@ -2030,7 +2931,7 @@ impl HQMServer {
                     let mut index = 0;
 
                     for i in self.game.game_players.iter() {
-                        if i.leaved_seconds != 0 {
+                        if i.leaved_seconds != 0 && !i.leave_exempt {
                             let mut ex = false;
                             for (player_index, player) in self.players.iter().enumerate() {
                                 if let Some(player) = player {
@@ -2052,24 +2953,41 @@ impl HQMServer {
                         self.game.game_players[i.to_owned()].leaved_seconds -= 1;
                         if self.game.game_players[i.to_owned()].leaved_seconds == 1 {
                             self.game.game_players[i.to_owned()].leaved_seconds = 0;
+                            self.game.game_players[i.to_owned()].left_game = true;
                             self.add_server_chat_message(format!(
-                                "{} lose 30 points",
-                                self.game.game_players[i.to_owned()].player_name_r
+                                "{} lose {} points",
+                                self.game.game_players[i.to_owned()].player_name_r,
+                                self.config.leave_penalty
                             ));
                         }
                     }
                 }
                 if self.game.time == 0 {
-                    if self.game.period != 4 {
+                    if self.game.period <= self.regulation_periods() {
                         if self.game.period != 0 || self.game.ranked_started {
                             self.game.period += 1;
+                            if self.game.period == 1 {
+                                self.clear_pucks();
+                            }
                         }
                     }
-                    if self.game.period > 3 && self.game.red_score != self.game.blue_score {
+                    let tie = self.game.red_score == self.game.blue_score;
+                    if self.game.period > self.regulation_periods()
+                        && (!tie || self.config.allow_ties)
+                    {
+                        if tie {
+                            self.add_server_chat_message(String::from("Game ended in a tie"));
+                        }
                         self.game.time_break = self.config.time_intermission * 100;
+                        self.game.break_reason = HQMBreakReason::GameOver;
                         self.game.game_over = true;
                     } else {
                         self.game.time_break = self.config.time_intermission * 100;
+                        self.game.break_reason = if self.game.period > self.regulation_periods() {
+                            HQMBreakReason::Shootout
+                        } else {
+                            HQMBreakReason::PeriodEnd
+                        };
                         self.game.next_faceoff_spot =
                             self.game.world.rink.center_faceoff_spot.clone();
                     }
@@ -2078,7 +2996,16 @@ impl HQMServer {
 
             if self.game.period == 0 && !self.game.ranked_started {
                 if self.game.logged_players.len() != 0 {
-                    if self.game.time == 1 {
+                    self.check_ranked_stall();
+
+                    // When the warmup clock runs out, give the current attempt up to 40 more
+                    // seconds; the warmup then ends as soon as that attempt is completed.
+                    // The extension is only granted once, so a stuck attempt cannot hold the
+                    // warmup open forever.
+                    if self.game.time == 1
+                        && self.config.mini_game_wait_for_end
+                        && !self.game.wait_for_end
+                    {
                         self.game.time = 4000;
                         self.game.wait_for_end = true;
                     }
@@ -2101,6 +3028,7 @@ impl HQMServer {
                             ));
                             self.add_server_chat_message(String::from("7.Long passes"));
                             self.game.time_break = 1300;
+                            self.game.break_reason = HQMBreakReason::MiniGameVote;
                             self.game.force_intermission = true;
                         }
 
@@ -2111,6 +3039,9 @@ impl HQMServer {
                             self.game.force_intermission = false;
                         }
                     } else {
+                        if self.game.mini_game_warmup == 0 && self.game.mini_game_time > 0 {
+                            self.remove_stalled_mini_game_pucks();
+                        }
                         match self.game.last_mini_game {
                             0 => {
                                 if self.game.mini_game_warmup > 0 {
@@ -2188,14 +3119,19 @@ impl HQMServer {
                                                     self.game.pucks_in_net.push(puck.index);
 
                                                     if self.game.pucks_in_net.len() > 6 {
-                                                        self.add_server_chat_message(format!(
-                                                            "Puck in net [{}/8] ({}.{})",
-                                                            self.game.pucks_in_net.len(),
-                                                            (3000 - self.game.mini_game_time) / 100,
-                                                            (3000 - self.game.mini_game_time) % 100
-                                                        ));
+                                                        self.add_mini_game_result_message(
+                                                            format!(
+                                                                "Puck in net [{}/8] ({}.{})",
+                                                                self.game.pucks_in_net.len(),
+                                                                (3000 - self.game.mini_game_time)
+                                                                    / 100,
+                                                                (3000 - self.game.mini_game_time)
+                                                                    % 100
+                                                            ),
+                                                            true,
+                                                        );
                                                     } else {
-                                                        self.add_directed_server_chat_message(
+                                                        self.add_mini_game_result_message(
                                                             format!(
                                                                 "Puck in net [{}/8] ({}.{})",
                                                                 self.game.pucks_in_net.len(),
@@ -2204,7 +3140,7 @@ impl HQMServer {
                                                                 (3000 - self.game.mini_game_time)
                                                                     % 100
                                                             ),
-                                                            self.game.next_game_player_index,
+                                                            false,
                                                         );
                                                     }
 
@@ -2220,9 +3156,10 @@ impl HQMServer {
                                                             result,
                                                         );
 
-                                                        self.add_server_chat_message(format!(
-                                                            "Result saved"
-                                                        ));
+                                                        self.add_mini_game_result_message(
+                                                            format!("Result saved"),
+                                                            true,
+                                                        );
 
                                                         if self.game.wait_for_end {
                                                             self.game.time = 0;
@@ -2272,7 +3209,7 @@ impl HQMServer {
                                             self.game.gk_catches = 0;
                                             self.game.gk_last_height = 2;
                                             self.game.gk_last_vector = 2;
-                                            self.game.gk_speed = 0.3;
+                                            self.game.gk_speed = self.config.goalie_start_speed;
                                             self.game.gk_puck_in_net = false;
 
                                             self.add_server_chat_message(format!(
@@ -2315,7 +3252,9 @@ impl HQMServer {
                                                     {
                                                         x_vec = self.game.gk_vectors
                                                             [self.game.gk_last_vector]
-                                                            + (self.game.gk_speed - 0.3) / 100.0;
+                                                            + (self.game.gk_speed
+                                                                - self.config.goalie_start_speed)
+                                                                / 100.0;
                                                     } else {
                                                         x_vec = self.game.gk_vectors
                                                             [self.game.gk_last_vector];
@@ -2345,14 +3284,18 @@ impl HQMServer {
                                             if self.game.gk_catches != 0
                                                 && self.game.gk_catches % 5 == 0
                                             {
-                                                self.add_server_chat_message(format!(
-                                                    "{} pucks caught",
-                                                    self.game.gk_catches
-                                                ));
+                                                self.add_mini_game_result_message(
+                                                    format!(
+                                                        "{} pucks caught",
+                                                        self.game.gk_catches
+                                                    ),
+                                                    true,
+                                                );
                                             }
 
                                             self.game.gk_catches += 1;
-                                            self.game.gk_speed += 0.02;
+                                            self.game.gk_speed +=
+                                                self.config.goalie_speed_increment;
                                         }
 
                                         let mut pucks = vec![];
@@ -2375,10 +3318,13 @@ impl HQMServer {
                                                     (self.game.gk_catches - 1).to_string(),
                                                 );
 
-                                                self.add_server_chat_message(format!(
-                                                    "{} pucks caught, result saved",
-                                                    (self.game.gk_catches - 1)
-                                                ));
+                                                self.add_mini_game_result_message(
+                                                    format!(
+                                                        "{} pucks caught, result saved",
+                                                        (self.game.gk_catches - 1)
+                                                    ),
+                                                    true,
+                                                );
 
                                                 if self.game.wait_for_end {
                                                     self.game.time = 0;
@@ -2488,10 +3434,13 @@ impl HQMServer {
                                                         (self.game.gk_catches - 1).to_string(),
                                                     );
 
-                                                    self.add_server_chat_message(format!(
-                                                        "{} goals, result saved",
-                                                        (self.game.gk_catches - 1)
-                                                    ));
+                                                    self.add_mini_game_result_message(
+                                                        format!(
+                                                            "{} goals, result saved",
+                                                            (self.game.gk_catches - 1)
+                                                        ),
+                                                        true,
+                                                    );
                                                 }
 
                                                 if self.game.wait_for_end {
@@ -2515,10 +3464,10 @@ impl HQMServer {
                                             if result == 1 {
                                                 self.game.gk_puck_in_net = true;
                                                 if self.game.gk_catches > 1 {
-                                                    self.add_server_chat_message(format!(
-                                                        "{} goals",
-                                                        self.game.gk_catches
-                                                    ));
+                                                    self.add_mini_game_result_message(
+                                                        format!("{} goals", self.game.gk_catches),
+                                                        true,
+                                                    );
                                                 }
 
                                                 self.game.world.objects[puck.index] =
@@ -2609,10 +3558,13 @@ impl HQMServer {
                                                     (30000 - self.game.mini_game_time) % 100
                                                 );
 
-                                                self.add_server_chat_message(format!(
-                                                    "Puck was on air {}, result saved",
-                                                    result.to_string()
-                                                ));
+                                                self.add_mini_game_result_message(
+                                                    format!(
+                                                        "Puck was on air {}, result saved",
+                                                        result.to_string()
+                                                    ),
+                                                    true,
+                                                );
 
                                                 Self::save_air_mini_game_result(
                                                     &self.game.next_game_player,
@@ -2640,10 +3592,13 @@ impl HQMServer {
                                                         (30000 - self.game.mini_game_time) / 100,
                                                         (30000 - self.game.mini_game_time) % 100
                                                     );
-                                                    self.add_server_chat_message(format!(
-                                                        "Puck was on air {}, result saved",
-                                                        result.to_string()
-                                                    ));
+                                                    self.add_mini_game_result_message(
+                                                        format!(
+                                                            "Puck was on air {}, result saved",
+                                                            result.to_string()
+                                                        ),
+                                                        true,
+                                                    );
                                                     Self::save_air_mini_game_result(
                                                         &self.game.next_game_player,
                                                         result,
@@ -2668,10 +3623,10 @@ impl HQMServer {
                                             );
 
                                             if (30000 - self.game.mini_game_time) / 100 != 0 {
-                                                self.add_server_chat_message(format!(
-                                                    "Puck on air {}",
-                                                    result.to_string()
-                                                ));
+                                                self.add_mini_game_result_message(
+                                                    format!("Puck on air {}", result.to_string()),
+                                                    true,
+                                                );
                                             }
                                         }
 
@@ -2785,10 +3740,13 @@ impl HQMServer {
                                                         (self.game.gk_catches - 1).to_string(),
                                                     );
 
-                                                    self.add_server_chat_message(format!(
-                                                        "{} goals, result saved",
-                                                        (self.game.gk_catches - 1)
-                                                    ));
+                                                    self.add_mini_game_result_message(
+                                                        format!(
+                                                            "{} goals, result saved",
+                                                            (self.game.gk_catches - 1)
+                                                        ),
+                                                        true,
+                                                    );
                                                 }
 
                                                 if self.game.wait_for_end {
@@ -2812,10 +3770,10 @@ impl HQMServer {
                                             if result == 1 {
                                                 self.game.gk_puck_in_net = true;
                                                 if self.game.gk_catches > 1 {
-                                                    self.add_server_chat_message(format!(
-                                                        "{} goals",
-                                                        self.game.gk_catches
-                                                    ));
+                                                    self.add_mini_game_result_message(
+                                                        format!("{} goals", self.game.gk_catches),
+                                                        true,
+                                                    );
                                                 }
 
                                                 self.game.world.objects[puck.index] =
@@ -2930,10 +3888,13 @@ impl HQMServer {
                                                         (self.game.gk_catches - 1).to_string(),
                                                     );
 
-                                                    self.add_server_chat_message(format!(
-                                                        "{} hits, result saved",
-                                                        (self.game.gk_catches - 1)
-                                                    ));
+                                                    self.add_mini_game_result_message(
+                                                        format!(
+                                                            "{} hits, result saved",
+                                                            (self.game.gk_catches - 1)
+                                                        ),
+                                                        true,
+                                                    );
                                                 }
 
                                                 if self.game.wait_for_end {
@@ -2956,10 +3917,13 @@ impl HQMServer {
                                                     let result = self.check_puck_in_square(puck);
                                                     if result == 1 {
                                                         self.game.gk_puck_in_net = true;
-                                                        self.add_server_chat_message(format!(
-                                                            "{} hits",
-                                                            self.game.gk_catches
-                                                        ));
+                                                        self.add_mini_game_result_message(
+                                                            format!(
+                                                                "{} hits",
+                                                                self.game.gk_catches
+                                                            ),
+                                                            true,
+                                                        );
 
                                                         self.game.sent = true;
                                                     }
@@ -3076,10 +4040,13 @@ impl HQMServer {
                                                         (self.game.gk_catches - 1).to_string(),
                                                     );
 
-                                                    self.add_server_chat_message(format!(
-                                                        "{} passes, result saved",
-                                                        (self.game.gk_catches - 1)
-                                                    ));
+                                                    self.add_mini_game_result_message(
+                                                        format!(
+                                                            "{} passes, result saved",
+                                                            (self.game.gk_catches - 1)
+                                                        ),
+                                                        true,
+                                                    );
                                                 }
 
                                                 if self.game.wait_for_end {
@@ -3103,10 +4070,13 @@ impl HQMServer {
                                                         self.check_puck_passed_in_square(puck);
                                                     if result == 1 {
                                                         self.game.gk_puck_in_net = true;
-                                                        self.add_server_chat_message(format!(
-                                                            "{} passes",
-                                                            self.game.gk_catches
-                                                        ));
+                                                        self.add_mini_game_result_message(
+                                                            format!(
+                                                                "{} passes",
+                                                                self.game.gk_catches
+                                                            ),
+                                                            true,
+                                                        );
 
                                                         self.game.sent = true;
                                                     }
@@ -3388,18 +4358,28 @@ impl HQMServer {
         let addr = SocketAddr::from(([0, 0, 0, 0], self.config.port));
 
         let socket = Arc::new(tokio::net::UdpSocket::bind(&addr).await?);
+        self.local_addr = socket.local_addr().ok();
         info!(
             "Server listening at address {:?}",
             socket.local_addr().unwrap()
         );
 
-        if self.config.public {
+        {
             let socket = socket.clone();
+            // Always running, /public turns announcing on and off at runtime
+            let public = self.public.clone();
             tokio::spawn(async move {
                 loop {
+                    if !public.load(Ordering::Relaxed) {
+                        tokio::time::sleep(Duration::from_secs(5)).await;
+                        continue;
+                    }
                     let master_server = get_master_server().await.ok();
                     if let Some(addr) = master_server {
                         for _ in 0..60 {
+                            if !public.load(Ordering::Relaxed) {
+                                break;
+                            }
                             let msg = b"Hock\x20";
                             let res = socket.send_to(msg, addr).await;
                             if res.is_err() {
@@ -3413,7 +4393,37 @@ impl HQMServer {
                 }
             });
         }
-        let (msg_sender, mut msg_receiver) = tokio::sync::mpsc::channel(256);
+        if self.config.admin_audit_log {
+            // A single writer keeps the audit lines in the order they were issued
+            let (audit_sender, mut audit_receiver) = mpsc::unbounded_channel::<String>();
+            self.audit_sender = Some(audit_sender);
+            tokio::spawn(async move {
+                let file = match tokio::fs::create_dir_all("logs").await {
+                    Ok(_) => {
+                        tokio::fs::OpenOptions::new()
+                            .create(true)
+                            .append(true)
+                            .open("logs/admin-audit.log")
+                            .await
+                    }
+                    Err(e) => Err(e),
+                };
+                let mut file = match file {
+                    Ok(file) => file,
+                    Err(e) => {
+                        warn!("Could not open admin audit log: {}", e);
+                        return;
+                    }
+                };
+                while let Some(line) = audit_receiver.recv().await {
+                    if let Err(e) = file.write_all(line.as_bytes()).await {
+                        warn!("Could not write admin audit log: {}", e);
+                    }
+                }
+            });
+        }
+        let (msg_sender, mut msg_receiver) = mpsc::channel(256);
+        self.msg_sender = Some(msg_sender.clone());
         {
             let socket = socket.clone();
 
@@ -3444,11 +4454,21 @@ impl HQMServer {
                     self.tick(& socket).await;
                 }
                 x = msg_receiver.recv() => {
-                    if let Some (HQMServerReceivedData::GameClientPacket {
-                        addr,
-                        data: msg
-                    }) = x {
-                        self.handle_message(addr, & socket, & msg).await;
+                    match x {
+                        Some (HQMServerReceivedData::GameClientPacket {
+                            addr,
+                            data: msg
+                        }) => {
+                            self.handle_message(addr, & socket, & msg).await;
+                        }
+                        Some (HQMServerReceivedData::DirectedServerChat {
+                            player_index,
+                            player_name,
+                            messages
+                        }) => {
+                            self.add_delayed_directed_server_chat_messages(player_index, &player_name, messages);
+                        }
+                        None => {}
                     }
                 }
             }
@@ -3461,9 +4481,22 @@ impl HQMServer {
             player_vec.push(None);
         }
 
+        let ban_list = match &config.bans_file {
+            Some(path) => match load_bans(path) {
+                Ok(ban_list) => ban_list,
+                Err(e) => {
+                    info!("Could not load bans from {}: {}", path, e);
+                    HashSet::new()
+                }
+            },
+            None => HashSet::new(),
+        };
+
+        let public = Arc::new(AtomicBool::new(config.public));
+
         HQMServer {
             players: player_vec,
-            ban_list: HashSet::new(),
+            ban_list,
             allow_join: true,
             game: HQMGame::new(1, &config),
             game_alloc: 1,
@@ -3471,8 +4504,74 @@ impl HQMServer {
             config,
             last_sec: 3,
             allow_ranked_join: true,
+            admin_paused: false,
+            tick_timings: HQMTickTimings::new(),
+            msg_sender: None,
+            audit_sender: None,
+            audit_command: None,
+            scheduled_messages: vec![],
+            local_addr: None,
+            rejected_versions: HashMap::new(),
+            public,
+            team_max_restore: None,
+        }
+    }
+}
+
+// One IP address per line, empty lines and lines starting with # are ignored
+pub(crate) fn load_bans(path: &str) -> std::io::Result<HashSet<IpAddr>> {
+    let contents = std::fs::read_to_string(path)?;
+    let mut ban_list = HashSet::new();
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
         }
+        match line.parse::<IpAddr>() {
+            Ok(ip) => {
+                ban_list.insert(ip);
+            }
+            Err(_) => {
+                info!("Ignoring invalid IP address {} in {}", line, path);
+            }
+        }
+    }
+    Ok(ban_list)
+}
+
+// Splits a message into pieces that fit in a chat message, without breaking characters
+pub(crate) fn split_chat_message(message: &str) -> Vec<String> {
+    let mut pieces = vec![];
+    let mut piece = String::new();
+    for c in message.chars() {
+        if piece.len() + c.len_utf8() > 63 {
+            pieces.push(std::mem::take(&mut piece));
+        }
+        piece.push(c);
+    }
+    if !piece.is_empty() {
+        pieces.push(piece);
     }
+    pieces
+}
+
+// Joins items with ", " into as few lines as fit in a chat message
+pub(crate) fn chat_lines(items: &[String]) -> Vec<String> {
+    let mut lines = vec![];
+    let mut line = String::new();
+    for item in items {
+        if !line.is_empty() && line.len() + 2 + item.len() > 63 {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push_str(", ");
+        }
+        line.push_str(item);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
 }
 
 fn has_players_in_offensive_zone(world: &HQMGameWorld, team: HQMTeam) -> bool {
@@ -3611,7 +4710,7 @@ fn write_objects(
                     _ => None,
                 });
                 writer.write_bits(1, 1);
-                writer.write_bits(2, 1); // Puck type
+                writer.write_bits(2, OBJECT_TYPE_PUCK);
                 writer.write_pos(17, puck.pos.0, old_puck.map(|puck| puck.pos.0));
                 writer.write_pos(17, puck.pos.1, old_puck.map(|puck| puck.pos.1));
                 writer.write_pos(17, puck.pos.2, old_puck.map(|puck| puck.pos.2));
@@ -3624,7 +4723,7 @@ fn write_objects(
                     _ => None,
                 });
                 writer.write_bits(1, 1);
-                writer.write_bits(2, 0); // Skater type
+                writer.write_bits(2, OBJECT_TYPE_SKATER);
                 writer.write_pos(17, skater.pos.0, old_skater.map(|skater| skater.pos.0));
                 writer.write_pos(17, skater.pos.1, old_skater.map(|skater| skater.pos.1));
                 writer.write_pos(17, skater.pos.2, old_skater.map(|skater| skater.pos.2));
@@ -4091,7 +5190,7 @@ fn get_player_name(bytes: Vec<u8>) -> Option<String> {
     return match String::from_utf8(bytes) {
         Ok(s) => {
             let s = s.trim();
-            let s = if s.is_empty() { "Noname" } else { s };
+            let s = if s.is_empty() { DEFAULT_PLAYER_NAME } else { s };
             Some(String::from(s))
         }
         Err(_) => None,
@@ -4126,12 +5225,15 @@ pub(crate) struct HQMConnectedPlayer {
     pub(crate) skater: Option<usize>,
     game_id: u32,
     input: HQMPlayerInput,
-    known_packet: u32,
+    pub(crate) known_packet: u32,
+    // Acks of packets older than this are ignored after /resync, so they are not used as a baseline
+    pub(crate) resync_packet: Option<u32>,
     known_msgpos: usize,
     chat_rep: Option<u8>,
     messages: Vec<Rc<HQMMessage>>,
-    inactivity: u32,
+    pub(crate) inactivity: u32,
     pub(crate) is_admin: bool,
+    pub(crate) is_head_admin: bool,
     pub(crate) is_muted: HQMMuteStatus,
     pub(crate) team_switch_timer: u32,
     hand: HQMSkaterHand,
@@ -4139,6 +5241,7 @@ pub(crate) struct HQMConnectedPlayer {
     deltatime: u32,
     last_ping: VecDeque<f32>,
     view_player_index: usize,
+    pub(crate) auto_view: bool,
 }
 
 impl HQMConnectedPlayer {
@@ -4156,12 +5259,14 @@ impl HQMConnectedPlayer {
             skater: None,
             game_id: u32::MAX,
             known_packet: u32::MAX,
+            resync_packet: None,
             known_msgpos: 0,
             chat_rep: None,
             messages: global_messages,
             input: HQMPlayerInput::default(),
             inactivity: 0,
             is_admin: false,
+            is_head_admin: false,
             is_muted: HQMMuteStatus::NotMuted,
             hand: HQMSkaterHand::Right,
             team_switch_timer: 0,
@@ -4169,6 +5274,7 @@ impl HQMConnectedPlayer {
             deltatime: 0,
             last_ping: VecDeque::new(),
             view_player_index: player_index,
+            auto_view: false,
             mass: 1.0,
         }
     }
@@ -4207,6 +5313,19 @@ pub enum HQMOffsideConfiguration {
     Immediate,
 }
 
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub enum HQMMiniGameResultsConfiguration {
+    Default,
+    Broadcast,
+    Private,
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub enum HQMShootoutOrder {
+    RoundRobin,
+    Random,
+}
+
 #[derive(Eq, PartialEq, Debug, Copy, Clone)]
 pub enum HQMSpawnPoint {
     Center,
@@ -4225,11 +5344,26 @@ pub(crate) struct HQMServerConfiguration {
     pub(crate) public: bool,
     pub(crate) player_max: usize,
     pub(crate) team_max: usize,
+    pub(crate) red_max: Option<usize>,
+    pub(crate) blue_max: Option<usize>,
     pub(crate) force_team_size_parity: bool,
+    pub(crate) spectators_see_chat: bool,
+    pub(crate) accepted_versions: Vec<u32>,
+    pub(crate) pause_without_admin: bool,
+    pub(crate) auto_view_interval: u32,
+    pub(crate) tick_budget_ms: u64,
+    pub(crate) ping_sample_count: usize,
+    pub(crate) recover_from_panic: bool,
+    pub(crate) admin_audit_log: bool,
+    pub(crate) restrict_view: bool,
+    pub(crate) mini_game_wait_for_end: bool,
+    pub(crate) mini_game_results: HQMMiniGameResultsConfiguration,
     pub(crate) welcome: Vec<String>,
     pub(crate) mode: HQMServerMode,
 
     pub(crate) password: String,
+    pub(crate) head_password: Option<String>,
+    pub(crate) bans_file: Option<String>,
 
     pub(crate) time_period: u32,
     pub(crate) time_warmup: u32,
@@ -4239,12 +5373,351 @@ pub(crate) struct HQMServerConfiguration {
     pub(crate) icing: HQMIcingConfiguration,
     pub(crate) warmup_pucks: usize,
     pub(crate) mercy_rule: u32,
+    pub(crate) ranked_min_points: usize,
+    pub(crate) ranked_count: usize,
+    pub(crate) ranked_stall_warning: u32,
+    pub(crate) ranked_login_open: bool,
+    pub(crate) ranked_periods: u32,
+    pub(crate) allow_ties: bool,
+    pub(crate) award_ranked_points: bool,
+    pub(crate) ranked_win_points: u32,
+    pub(crate) ranked_loss_points: u32,
+    pub(crate) assists_enabled: bool,
+    pub(crate) stats_show_ratio: bool,
+    pub(crate) timeout_leave_penalty: bool,
+    pub(crate) leave_penalty: u32,
+    pub(crate) goal_break: bool,
+    pub(crate) offside_warning_ticks: u32,
+    pub(crate) goalie_start_speed: f32,
+    pub(crate) goalie_speed_increment: f32,
+    pub(crate) mini_game_stall_time: u32,
+    pub(crate) shootout_order: HQMShootoutOrder,
+    pub(crate) shootout_decision_time: u32,
+    pub(crate) shootout_setup_time: u32,
+    pub(crate) shootout_attempt_time: u32,
     pub(crate) limit_jump_speed: bool,
 
     pub(crate) cheats_enabled: bool,
 
     pub(crate) replays_enabled: bool,
+    pub(crate) replay_dir: String,
 
     pub(crate) spawn_point: HQMSpawnPoint,
     pub(crate) cylinder_puck_post_collision: bool,
 }
+
+impl HQMServerConfiguration {
+    pub(crate) fn team_max_for(&self, team: HQMTeam) -> usize {
+        match team {
+            HQMTeam::Red => self.red_max.unwrap_or(self.team_max),
+            HQMTeam::Blue => self.blue_max.unwrap_or(self.team_max),
+        }
+    }
+
+    // The shootout decision and setup windows run inside time_intermission and have to leave some of
+    // it over. Shortens them if they don't and returns the configured values.
+    pub(crate) fn clamp_shootout_times(&mut self) -> Option<(u32, u32)> {
+        let configured = (self.shootout_decision_time, self.shootout_setup_time);
+        if configured.0.saturating_add(configured.1) < self.time_intermission {
+            return None;
+        }
+        let longest = self.time_intermission.saturating_sub(1);
+        self.shootout_decision_time = configured.0.min(longest);
+        self.shootout_setup_time = longest - self.shootout_decision_time;
+        Some(configured)
+    }
+
+    // Every field as name=value for /configall, passwords are hidden
+    pub(crate) fn entries(&self) -> Vec<String> {
+        let hidden = |set: bool| if set { "<set>" } else { "<unset>" };
+        vec![
+            format!("server_name={}", self.server_name),
+            format!("port={}", self.port),
+            format!("public={}", self.public),
+            format!("player_max={}", self.player_max),
+            format!("team_max={}", self.team_max),
+            format!("red_max={:?}", self.red_max),
+            format!("blue_max={:?}", self.blue_max),
+            format!("force_team_size_parity={}", self.force_team_size_parity),
+            format!("spectators_see_chat={}", self.spectators_see_chat),
+            format!("accepted_versions={:?}", self.accepted_versions),
+            format!("pause_without_admin={}", self.pause_without_admin),
+            format!("auto_view_interval={}", self.auto_view_interval),
+            format!("tick_budget_ms={}", self.tick_budget_ms),
+            format!("ping_sample_count={}", self.ping_sample_count),
+            format!("recover_from_panic={}", self.recover_from_panic),
+            format!("admin_audit_log={}", self.admin_audit_log),
+            format!("restrict_view={}", self.restrict_view),
+            format!("mini_game_wait_for_end={}", self.mini_game_wait_for_end),
+            format!("mini_game_results={:?}", self.mini_game_results),
+            format!("welcome={} lines", self.welcome.len()),
+            format!("mode={:?}", self.mode),
+            format!("password={}", hidden(!self.password.is_empty())),
+            format!("head_password={}", hidden(self.head_password.is_some())),
+            format!("bans_file={:?}", self.bans_file),
+            format!("time_period={}", self.time_period),
+            format!("time_warmup={}", self.time_warmup),
+            format!("time_break={}", self.time_break),
+            format!("time_intermission={}", self.time_intermission),
+            format!("offside={:?}", self.offside),
+            format!("icing={:?}", self.icing),
+            format!("warmup_pucks={}", self.warmup_pucks),
+            format!("mercy_rule={}", self.mercy_rule),
+            format!("ranked_min_points={}", self.ranked_min_points),
+            format!("ranked_count={}", self.ranked_count),
+            format!("ranked_stall_warning={}", self.ranked_stall_warning),
+            format!("ranked_login_open={}", self.ranked_login_open),
+            format!("ranked_periods={}", self.ranked_periods),
+            format!("allow_ties={}", self.allow_ties),
+            format!("award_ranked_points={}", self.award_ranked_points),
+            format!("ranked_win_points={}", self.ranked_win_points),
+            format!("ranked_loss_points={}", self.ranked_loss_points),
+            format!("assists_enabled={}", self.assists_enabled),
+            format!("stats_show_ratio={}", self.stats_show_ratio),
+            format!("timeout_leave_penalty={}", self.timeout_leave_penalty),
+            format!("leave_penalty={}", self.leave_penalty),
+            format!("goal_break={}", self.goal_break),
+            format!("offside_warning_ticks={}", self.offside_warning_ticks),
+            format!("goalie_start_speed={}", self.goalie_start_speed),
+            format!("goalie_speed_increment={}", self.goalie_speed_increment),
+            format!("mini_game_stall_time={}", self.mini_game_stall_time),
+            format!("shootout_order={:?}", self.shootout_order),
+            format!("shootout_decision_time={}", self.shootout_decision_time),
+            format!("shootout_setup_time={}", self.shootout_setup_time),
+            format!("shootout_attempt_time={}", self.shootout_attempt_time),
+            format!("limit_jump_speed={}", self.limit_jump_speed),
+            format!("cheats_enabled={}", self.cheats_enabled),
+            format!("replays_enabled={}", self.replays_enabled),
+            format!("replay_dir={}", self.replay_dir),
+            format!("spawn_point={:?}", self.spawn_point),
+            format!(
+                "cylinder_puck_post_collision={}",
+                self.cylinder_puck_post_collision
+            ),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hqm_game::RHQMGamePlayer;
+
+    fn test_config() -> HQMServerConfiguration {
+        HQMServerConfiguration {
+            server_name: String::from("Test"),
+            port: 27585,
+            public: false,
+            player_max: 20,
+            team_max: 5,
+            red_max: None,
+            blue_max: None,
+            force_team_size_parity: false,
+            spectators_see_chat: true,
+            accepted_versions: vec![55],
+            pause_without_admin: false,
+            auto_view_interval: 5,
+            tick_budget_ms: 10,
+            ping_sample_count: 100,
+            recover_from_panic: false,
+            admin_audit_log: false,
+            restrict_view: false,
+            mini_game_wait_for_end: true,
+            mini_game_results: HQMMiniGameResultsConfiguration::Default,
+            welcome: vec![],
+            mode: HQMServerMode::Match,
+            password: String::from("admin"),
+            head_password: None,
+            bans_file: None,
+            time_period: 300,
+            time_warmup: 300,
+            time_break: 10,
+            time_intermission: 20,
+            offside: HQMOffsideConfiguration::Delayed,
+            icing: HQMIcingConfiguration::Off,
+            warmup_pucks: 3,
+            mercy_rule: 0,
+            ranked_min_points: 0,
+            ranked_count: 4,
+            ranked_stall_warning: 300,
+            ranked_login_open: true,
+            ranked_periods: 3,
+            allow_ties: false,
+            award_ranked_points: false,
+            ranked_win_points: 10,
+            ranked_loss_points: 10,
+            assists_enabled: true,
+            stats_show_ratio: false,
+            timeout_leave_penalty: true,
+            leave_penalty: 30,
+            goal_break: true,
+            offside_warning_ticks: 0,
+            goalie_start_speed: 0.3,
+            goalie_speed_increment: 0.02,
+            mini_game_stall_time: 0,
+            shootout_order: HQMShootoutOrder::RoundRobin,
+            shootout_decision_time: 3,
+            shootout_setup_time: 2,
+            shootout_attempt_time: 15,
+            limit_jump_speed: false,
+            cheats_enabled: false,
+            replays_enabled: false,
+            replay_dir: String::from("replays"),
+            spawn_point: HQMSpawnPoint::Center,
+            cylinder_puck_post_collision: false,
+        }
+    }
+
+    fn connect(server: &mut HQMServer, player_index: usize, name: &str) {
+        let addr: SocketAddr = format!("127.0.0.1:{}", 30000 + player_index)
+            .parse()
+            .unwrap();
+        server.players[player_index] = Some(HQMConnectedPlayer::new(
+            player_index,
+            String::from(name),
+            addr,
+            vec![],
+        ));
+    }
+
+    fn game_player(name: &str, player_index: usize, team: usize) -> RHQMGamePlayer {
+        RHQMGamePlayer {
+            player_name_r: String::from(name),
+            player_i_r: player_index,
+            player_points: 0,
+            player_team: team,
+            goals: 0,
+            assists: 0,
+            leaved_seconds: 0,
+            leave_exempt: false,
+            left_game: false,
+        }
+    }
+
+    fn skater_team(server: &HQMServer, player_index: usize) -> Option<HQMTeam> {
+        let player = server.players[player_index].as_ref()?;
+        match &server.game.world.objects[player.skater?] {
+            HQMGameObject::Player(skater) => Some(skater.team),
+            _ => None,
+        }
+    }
+
+    fn puck_count(server: &HQMServer) -> usize {
+        server
+            .game
+            .world
+            .objects
+            .iter()
+            .filter(|object| matches!(object, HQMGameObject::Puck(_)))
+            .count()
+    }
+
+    #[test]
+    fn ranked_teams_survive_faceoff() {
+        let mut server = HQMServer::new(test_config());
+        server.game.ranked_started = true;
+        server.game.period = 1;
+
+        // Both players rejoined as spectators, one of them in a different slot
+        connect(&mut server, 0, "RedPlayer");
+        connect(&mut server, 3, "BluePlayer");
+        server.game.game_players = vec![
+            game_player("RedPlayer", 0, 0),
+            game_player("BluePlayer", 1, 1),
+        ];
+
+        server.do_faceoff();
+
+        assert_eq!(skater_team(&server, 0), Some(HQMTeam::Red));
+        assert_eq!(skater_team(&server, 3), Some(HQMTeam::Blue));
+        assert_eq!(server.game.game_players[1].player_i_r, 3);
+    }
+
+    #[test]
+    fn ranked_teams_ignore_team_max() {
+        let mut config = test_config();
+        config.team_max = 1;
+        let mut server = HQMServer::new(config);
+        server.game.ranked_count = 4;
+        server.game.ranked_started = true;
+
+        let names = ["First", "Second", "Third", "Fourth"];
+        for (player_index, name) in names.iter().enumerate() {
+            connect(&mut server, player_index, name);
+            server
+                .game
+                .game_players
+                .push(game_player(name, player_index, 0));
+        }
+
+        server.set_teams_by_server(0);
+
+        let teams: Vec<Option<HQMTeam>> = (0..4).map(|i| skater_team(&server, i)).collect();
+        let red = teams.iter().filter(|x| **x == Some(HQMTeam::Red)).count();
+        let blue = teams.iter().filter(|x| **x == Some(HQMTeam::Blue)).count();
+        assert_eq!((red, blue), (2, 2));
+    }
+
+    #[test]
+    fn warmup_pucks_cleared_at_match_start() {
+        let mut server = HQMServer::new(test_config());
+        for i in 0..3 {
+            let pos = Point3::new(10.0 + i as f32, 1.5, 30.0);
+            server
+                .game
+                .world
+                .create_puck_object(pos, Matrix3::identity(), false);
+        }
+        assert_eq!(puck_count(&server), 3);
+
+        // Last tick of the warmup of a started ranked game
+        server.game.ranked_started = true;
+        server.game.paused = false;
+        server.game.period = 0;
+        server.game.time = 1;
+        server.update_clock();
+
+        assert_eq!(server.game.period, 1);
+        assert_eq!(puck_count(&server), 0);
+
+        // End of the intermission
+        server.game.time_break = 1;
+        server.update_clock();
+
+        assert_eq!(puck_count(&server), 1);
+    }
+
+    #[test]
+    fn timed_out_player_leaves_ranked_queue() {
+        let mut server = HQMServer::new(test_config());
+        connect(&mut server, 0, "Waiting");
+        server.game.logged_players.push(RHQMPlayer {
+            player_name: String::from("Waiting"),
+            player_i: 0,
+            afk: false,
+        });
+        if let Some(player) = &mut server.players[0] {
+            player.inactivity = 500;
+        }
+
+        server.update_players_and_input();
+
+        assert!(server.players[0].is_none());
+        assert!(server.game.logged_players.is_empty());
+    }
+
+    #[test]
+    fn shootout_times_fit_in_intermission() {
+        let mut config = test_config();
+        assert_eq!(config.clamp_shootout_times(), None);
+
+        config.time_intermission = 4;
+        config.shootout_decision_time = 3;
+        config.shootout_setup_time = 2;
+        assert_eq!(config.clamp_shootout_times(), Some((3, 2)));
+        assert_eq!(
+            (config.shootout_decision_time, config.shootout_setup_time),
+            (3, 0)
+        );
+    }
+}
@@ -4,7 +4,7 @@ use std::path::Path;
 extern crate ini;
 use ini::Ini;
 use std::env;
-use crate::hqm_server::{HQMServer, HQMServerConfiguration, HQMIcingConfiguration, HQMOffsideConfiguration, HQMServerMode, HQMSpawnPoint};
+use crate::hqm_server::{HQMServer, HQMServerConfiguration, HQMIcingConfiguration, HQMOffsideConfiguration, HQMServerMode, HQMSpawnPoint, HQMMiniGameResultsConfiguration, HQMShootoutOrder};
 
 mod hqm_parse;
 mod hqm_simulate;
@@ -12,6 +12,7 @@ mod hqm_game;
 mod hqm_server;
 mod hqm_admin_commands;
 
+use tracing::warn;
 use tracing_subscriber;
 use tracing_appender;
 
@@ -39,11 +40,50 @@ async fn main() -> std::io::Result<()> {
         let server_public = server_section.get("public").unwrap().parse::<bool>().unwrap();
         let server_player_max = server_section.get("player_max").unwrap().parse::<usize>().unwrap();
         let server_team_max = server_section.get("team_max").unwrap().parse::<usize>().unwrap();
+        let server_red_max = server_section.get("red_max").map(|x| x.parse::<usize>().unwrap());
+        let server_blue_max = server_section.get("blue_max").map(|x| x.parse::<usize>().unwrap());
         let force_team_size_parity = match server_section.get("force_team_size_parity") {
             Some(s) => s.eq_ignore_ascii_case("true"),
             None => false
         };
+        let pause_without_admin = match server_section.get("pause_without_admin") {
+            Some(s) => s.eq_ignore_ascii_case("true"),
+            None => false
+        };
+        let restrict_view = match server_section.get("restrict_view") {
+            Some(s) => s.eq_ignore_ascii_case("true"),
+            None => false
+        };
+        let spectators_see_chat = match server_section.get("spectators_see_chat") {
+            Some(s) => s.eq_ignore_ascii_case("true"),
+            None => true
+        };
+        let accepted_versions = server_section.get("accepted_versions").map_or(vec![55], |x| x.split(',')
+            .map(|version| version.trim().parse::<u32>().unwrap())
+            .collect());
+        let recover_from_panic = match server_section.get("recover_from_panic") {
+            Some(s) => s.eq_ignore_ascii_case("true"),
+            None => false
+        };
+        let admin_audit_log = match server_section.get("admin_audit_log") {
+            Some(s) => s.eq_ignore_ascii_case("true"),
+            None => false
+        };
+        let ping_sample_count = server_section.get("ping_sample_count").map_or(100, |x| x.parse::<usize>().unwrap()).max(1);
+        let tick_budget_ms = server_section.get("tick_budget_ms").map_or(10, |x| x.parse::<u64>().unwrap());
+        let auto_view_interval = server_section.get("auto_view_interval").map_or(5, |x| x.parse::<u32>().unwrap());
+        let mini_game_wait_for_end = match server_section.get("mini_game_wait_for_end") {
+            Some(s) => s.eq_ignore_ascii_case("true"),
+            None => true
+        };
+        let mini_game_results = server_section.get("mini_game_results").map_or(HQMMiniGameResultsConfiguration::Default, |x| match x {
+            "broadcast" => HQMMiniGameResultsConfiguration::Broadcast,
+            "private" => HQMMiniGameResultsConfiguration::Private,
+            _ => HQMMiniGameResultsConfiguration::Default
+        });
         let server_password = server_section.get("password").unwrap().parse::<String>().unwrap();
+        let head_password = server_section.get("head_password").map(String::from);
+        let bans_file = server_section.get("bans_file").map(String::from);
         let mode = server_section.get("mode").map_or(HQMServerMode::Match, |x| {
             match x {
                 "warmup" => HQMServerMode::PermanentWarmup,
@@ -56,6 +96,7 @@ async fn main() -> std::io::Result<()> {
             Some(s) => s.eq_ignore_ascii_case("true"),
             None => false
         };
+        let replay_dir = server_section.get("replay_dir").map_or(String::from("replays"), |x| String::from(x));
 
         let cheats_enabled = match server_section.get("cheats_enabled") {
             Some(s) => s.eq_ignore_ascii_case("true"),
@@ -79,6 +120,64 @@ async fn main() -> std::io::Result<()> {
         let warmup_pucks = game_section.get("warmup_pucks").map_or_else(|| 1, |x| x.parse::<usize>().unwrap());
         let mercy_rule = game_section.get("mercy_rule").map_or_else(|| 6, |x| x.parse::<u32>().unwrap());
 
+        let ranked_min_points = game_section.get("ranked_min_points").map_or(0, |x| x.parse::<usize>().unwrap());
+
+        let ranked_count = game_section.get("ranked_count").map_or(100, |x| x.parse::<usize>().unwrap());
+        let ranked_stall_warning = game_section.get("ranked_stall_warning").map_or(300, |x| x.parse::<u32>().unwrap());
+
+        let ranked_login_open = match game_section.get("ranked_login_open") {
+            Some(s) => s.eq_ignore_ascii_case("true"),
+            None => true
+        };
+
+        let ranked_periods = game_section.get("ranked_periods").map_or(3, |x| x.parse::<u32>().unwrap());
+        let allow_ties = match game_section.get("allow_ties") {
+            Some(s) => s.eq_ignore_ascii_case("true"),
+            None => false
+        };
+        let award_ranked_points = match game_section.get("award_ranked_points") {
+            Some(s) => s.eq_ignore_ascii_case("true"),
+            None => false
+        };
+        let ranked_win_points = game_section.get("ranked_win_points").map_or(10, |x| x.parse::<u32>().unwrap());
+        let ranked_loss_points = game_section.get("ranked_loss_points").map_or(10, |x| x.parse::<u32>().unwrap());
+
+        let assists_enabled = match game_section.get("assists_enabled") {
+            Some(s) => s.eq_ignore_ascii_case("true"),
+            None => true
+        };
+
+        let offside_warning_ticks = game_section.get("offside_warning_ticks").map_or(0, |x| x.parse::<u32>().unwrap());
+
+        let timeout_leave_penalty = match game_section.get("timeout_leave_penalty") {
+            Some(s) => s.eq_ignore_ascii_case("true"),
+            None => true
+        };
+        let leave_penalty = game_section.get("leave_penalty").map_or(30, |x| x.parse::<u32>().unwrap());
+
+        let stats_show_ratio = match game_section.get("stats_show_ratio") {
+            Some(s) => s.eq_ignore_ascii_case("true"),
+            None => false
+        };
+
+        let goal_break = match game_section.get("goal_break") {
+            Some(s) => s.eq_ignore_ascii_case("true"),
+            None => true
+        };
+
+        let goalie_start_speed = game_section.get("goalie_start_speed").map_or(0.3, |x| x.parse::<f32>().unwrap());
+        let mini_game_stall_time = game_section.get("mini_game_stall_time").map_or(0, |x| x.parse::<u32>().unwrap());
+        let goalie_speed_increment = game_section.get("goalie_speed_increment").map_or(0.02, |x| x.parse::<f32>().unwrap());
+
+        let shootout_decision_time = game_section.get("shootout_decision_time").map_or(3, |x| x.parse::<u32>().unwrap());
+        let shootout_setup_time = game_section.get("shootout_setup_time").map_or(2, |x| x.parse::<u32>().unwrap());
+        let shootout_attempt_time = game_section.get("shootout_attempt_time").map_or(15, |x| x.parse::<u32>().unwrap());
+
+        let shootout_order = game_section.get("shootout_order").map_or(HQMShootoutOrder::RoundRobin, |x| match x {
+            "random" => HQMShootoutOrder::Random,
+            _ => HQMShootoutOrder::RoundRobin
+        });
+
         let limit_jump_speed = match game_section.get("limit_jump_speed") {
             Some(s) => s.eq_ignore_ascii_case("true"),
             None => false
@@ -106,14 +205,18 @@ async fn main() -> std::io::Result<()> {
             _ => HQMSpawnPoint::Center
         });
 
-        let config = HQMServerConfiguration {
+        let mut config = HQMServerConfiguration {
             server_name,
             port: server_port,
             team_max: server_team_max,
+            red_max: server_red_max,
+            blue_max: server_blue_max,
             player_max: server_player_max,
             public: server_public,
 
             password: server_password,
+            head_password,
+            bans_file,
 
             time_period: rules_time_period, 
             time_warmup: rules_time_warmup, 
@@ -123,10 +226,44 @@ async fn main() -> std::io::Result<()> {
             offside,
             warmup_pucks,
             force_team_size_parity,
+            spectators_see_chat,
+            accepted_versions,
+            pause_without_admin,
+            auto_view_interval,
+            tick_budget_ms,
+            ping_sample_count,
+            recover_from_panic,
+            admin_audit_log,
+            restrict_view,
+            mini_game_wait_for_end,
+            mini_game_results,
             limit_jump_speed,
             mercy_rule,
+            ranked_min_points,
+            ranked_count,
+            ranked_stall_warning,
+            ranked_login_open,
+            ranked_periods,
+            allow_ties,
+            award_ranked_points,
+            ranked_win_points,
+            ranked_loss_points,
+            assists_enabled,
+            stats_show_ratio,
+            timeout_leave_penalty,
+            leave_penalty,
+            goal_break,
+            offside_warning_ticks,
+            shootout_order,
+            shootout_decision_time,
+            shootout_setup_time,
+            shootout_attempt_time,
+            goalie_start_speed,
+            goalie_speed_increment,
+            mini_game_stall_time,
             cheats_enabled,
             replays_enabled,
+            replay_dir,
             spawn_point,
             cylinder_puck_post_collision,
 
@@ -141,6 +278,17 @@ async fn main() -> std::io::Result<()> {
             .with_writer(non_blocking)
             .init();
 
+        if let Some((decision_time, setup_time)) = config.clamp_shootout_times() {
+            warn!(
+                shootout_decision_time = decision_time,
+                shootout_setup_time = setup_time,
+                time_intermission = config.time_intermission,
+                clamped_decision_time = config.shootout_decision_time,
+                clamped_setup_time = config.shootout_setup_time,
+                "Shootout windows don't fit in the intermission, shortened"
+            );
+        }
+
         // Config file didn't exist; use defaults as described
         return HQMServer::new(config).run().await;
     } else {